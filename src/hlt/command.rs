@@ -0,0 +1,78 @@
+use std::fmt;
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq,Hash)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Direction {
+    //(y,x) unit step, None for anything that is not a single cardinal step
+    pub fn from_delta( delta: (i32,i32) ) -> Option<Direction> {
+        match delta {
+            (-1,0) => Some(Direction::North),
+            (1,0) => Some(Direction::South),
+            (0,1) => Some(Direction::East),
+            (0,-1) => Some(Direction::West),
+            _ => None,
+        }
+    }
+
+    pub fn delta( & self ) -> (i32,i32) {
+        match self {
+            Direction::North => (-1,0),
+            Direction::South => (1,0),
+            Direction::East => (0,1),
+            Direction::West => (0,-1),
+        }
+    }
+
+    //letter of the direction in the engine's move commands and in replays
+    pub fn as_char( & self ) -> char {
+        match self {
+            Direction::North => 'n',
+            Direction::South => 's',
+            Direction::East => 'e',
+            Direction::West => 'w',
+        }
+    }
+
+    pub fn from_char( c: char ) -> Option<Direction> {
+        [ Direction::North, Direction::South, Direction::East, Direction::West ].iter().cloned().find(|d| d.as_char() == c )
+    }
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Command {
+    Move(usize,Direction), //ship id, direction
+    Stay(usize), //ship id
+    ConvertToDropoff(usize), //ship id
+    Spawn,
+}
+
+impl Command {
+    pub fn ship_id( & self ) -> Option<usize> {
+        match self {
+            Command::Move(id,_) | Command::Stay(id) | Command::ConvertToDropoff(id) => Some(*id),
+            Command::Spawn => None,
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt( &self, f: & mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            Command::Move(id,dir) => write!( f, "m {} {}", id, dir.as_char() ),
+            Command::Stay(id) => write!( f, "m {} o", id ),
+            Command::ConvertToDropoff(id) => write!( f, "c {}", id ),
+            Command::Spawn => write!( f, "g" ),
+        }
+    }
+}
+
+//one line of output for the engine, terminated by the caller
+pub fn serialize( commands: &[Command] ) -> String {
+    commands.iter().map(|x| x.to_string() ).collect::<Vec<_>>().join(" ")
+}
@@ -1,8 +1,9 @@
-use hlt::log::Log;
+use hlt::error::{Error, Result};
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::fmt;
 
+#[derive(Clone)]
 pub struct Constants {
     pub max_halite: usize,
     pub ship_cost: usize,
@@ -16,16 +17,17 @@ pub struct Constants {
     pub inspired_extract_ratio: usize,
     pub inspired_bonus_multiplier: f64,
     pub inspired_move_cost_ratio: usize,
+    pub initial_halite: usize, //what every player's bank holds at the start
 }
 
 impl Constants {
-    pub fn new(log: &mut Log, string_from_engine: &str) -> Constants {
+    pub fn new(string_from_engine: &str) -> Result<Constants> {
         let token_iter = string_from_engine.split(|c| " {},:\"\r\n".contains(c));
         let token_iter = token_iter.filter(|x| !x.is_empty());
         let tokens: Vec<&str> = token_iter.collect();
 
         if (tokens.len() % 2) != 0 {
-            log.panic("Error: constants: expected even total number of key and value tokens from server.");
+            return Err(Error::InvalidFrame("constants: expected even total number of key and value tokens from server".to_string()));
         }
 
         let mut map = HashMap::new();
@@ -34,41 +36,63 @@ impl Constants {
             map.insert(tokens[i].to_string(), tokens[i+1].to_string());
         }
 
-        Constants {
-            ship_cost: Constants::get_value(log, &map, "NEW_ENTITY_ENERGY_COST"),
-            dropoff_cost: Constants::get_value(log, &map, "DROPOFF_COST"),
-            max_halite: Constants::get_value(log, &map, "MAX_ENERGY"),
-            max_turns: Constants::get_value(log, &map, "MAX_TURNS"),
-            extract_ratio: Constants::get_value(log, &map, "EXTRACT_RATIO"),
-            move_cost_ratio: Constants::get_value(log, &map, "MOVE_COST_RATIO"),
-            inspiration_enabled: Constants::get_value(log, &map, "INSPIRATION_ENABLED"),
-            inspiration_radius: Constants::get_value(log, &map, "INSPIRATION_RADIUS"),
-            inspiration_ship_count: Constants::get_value(log, &map, "INSPIRATION_SHIP_COUNT"),
-            inspired_extract_ratio: Constants::get_value(log, &map, "INSPIRED_EXTRACT_RATIO"),
-            inspired_bonus_multiplier: Constants::get_value(log, &map, "INSPIRED_BONUS_MULTIPLIER"),
-            inspired_move_cost_ratio: Constants::get_value(log, &map, "INSPIRED_MOVE_COST_RATIO"),
-        }
+        Ok(Constants {
+            ship_cost: Constants::get_value(&map, "NEW_ENTITY_ENERGY_COST")?,
+            dropoff_cost: Constants::get_value(&map, "DROPOFF_COST")?,
+            max_halite: Constants::get_value(&map, "MAX_ENERGY")?,
+            max_turns: Constants::get_value(&map, "MAX_TURNS")?,
+            extract_ratio: Constants::get_value(&map, "EXTRACT_RATIO")?,
+            move_cost_ratio: Constants::get_value(&map, "MOVE_COST_RATIO")?,
+            inspiration_enabled: Constants::get_value(&map, "INSPIRATION_ENABLED")?,
+            inspiration_radius: Constants::get_value(&map, "INSPIRATION_RADIUS")?,
+            inspiration_ship_count: Constants::get_value(&map, "INSPIRATION_SHIP_COUNT")?,
+            inspired_extract_ratio: Constants::get_value(&map, "INSPIRED_EXTRACT_RATIO")?,
+            inspired_bonus_multiplier: Constants::get_value(&map, "INSPIRED_BONUS_MULTIPLIER")?,
+            inspired_move_cost_ratio: Constants::get_value(&map, "INSPIRED_MOVE_COST_RATIO")?,
+            initial_halite: Constants::get_value(&map, "INITIAL_ENERGY")?,
+        })
     }
 
-    fn get_value<T: FromStr>(log: &mut Log, map: &HashMap<String, String>, key: &str) -> T {
-        let s = Constants::get_string(log, map, key);
+    fn get_value<T: FromStr>(map: &HashMap<String, String>, key: &str) -> Result<T> {
+        let s = Constants::get_string(map, key)?;
         match s.parse::<T>() {
-            Ok(x) => x,
-            Err(_) => log.panic(&format!("Error: constants: for {} got '{}' from server and failed to parse that.", key, s))
+            Ok(x) => Ok(x),
+            Err(_) => Err(Error::Parse { token: s.clone(), expected: format!("value of {}", key) })
         }
     }
 
-    fn get_string<'a>(log: &mut Log, map: &'a HashMap<String, String>, key: &str) -> &'a String {
+    fn get_string<'a>(map: &'a HashMap<String, String>, key: &str) -> Result<&'a String> {
         match map.get(key) {
-            Some(x) => x,
-            None => log.panic(&format!("Error: constants: server did not send {} constant.", key))
+            Some(x) => Ok(x),
+            None => Err(Error::MissingConstant(key.to_string()))
+        }
+    }
+}
+
+//values the official engine uses unless overridden on its command line
+impl Default for Constants {
+    fn default() -> Constants {
+        Constants {
+            max_halite: 1000,
+            ship_cost: 1000,
+            dropoff_cost: 4000,
+            max_turns: 400,
+            extract_ratio: 4,
+            move_cost_ratio: 10,
+            inspiration_enabled: true,
+            inspiration_radius: 4,
+            inspiration_ship_count: 2,
+            inspired_extract_ratio: 4,
+            inspired_bonus_multiplier: 2.,
+            inspired_move_cost_ratio: 10,
+            initial_halite: 5000,
         }
     }
 }
 
 impl fmt::Display for Constants {
     fn fmt( &self, f: & mut fmt::Formatter ) -> fmt::Result {
-        write!( f, "max_halite: {}, ship_cost: {}, dropoff_cost: {}, max_turns: {}, extract_ratio: {}, move_cost_ratio: {}, inspi_enab: {}, inspi_radius: {}, inspi_ship_count: {}, inspi_extract_ratio: {}, inspi_bonus_mult: {}, inspi_move_cost_ratio: {}", self.max_halite, self.ship_cost, self.dropoff_cost, self.max_turns, self.extract_ratio, self.move_cost_ratio, self.inspiration_enabled, self.inspiration_radius, self.inspiration_ship_count, self.inspired_extract_ratio, self.inspired_bonus_multiplier, self.inspired_move_cost_ratio )
+        write!( f, "max_halite: {}, ship_cost: {}, dropoff_cost: {}, max_turns: {}, extract_ratio: {}, move_cost_ratio: {}, inspi_enab: {}, inspi_radius: {}, inspi_ship_count: {}, inspi_extract_ratio: {}, inspi_bonus_mult: {}, inspi_move_cost_ratio: {}, initial_halite: {}", self.max_halite, self.ship_cost, self.dropoff_cost, self.max_turns, self.extract_ratio, self.move_cost_ratio, self.inspiration_enabled, self.inspiration_radius, self.inspiration_ship_count, self.inspired_extract_ratio, self.inspired_bonus_multiplier, self.inspired_move_cost_ratio, self.initial_halite )
                 
     }
 }
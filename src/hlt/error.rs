@@ -0,0 +1,38 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    MissingConstant(String), //constant key
+    Parse { token: String, expected: String },
+    TruncatedFrame(String), //what was being read when the tokens ran out
+    InvalidFrame(String), //tokens parsed but values are inconsistent
+    Desync(String), //counts too inconsistent to tell where the frame ends
+    UnexpectedEof,
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt( &self, f: & mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            Error::MissingConstant(key) => write!( f, "server did not send {} constant", key ),
+            Error::Parse { token, expected } => write!( f, "can't parse '{}' as {}", token, expected ),
+            Error::TruncatedFrame(what) => write!( f, "frame truncated while reading {}", what ),
+            Error::InvalidFrame(msg) => write!( f, "invalid frame: {}", msg ),
+            Error::Desync(msg) => write!( f, "lost track of the frame: {}", msg ),
+            Error::UnexpectedEof => write!( f, "input connection from server closed" ),
+            Error::Io(e) => write!( f, "input error: {}", e ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from( e: io::Error ) -> Error {
+        Error::Io(e)
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
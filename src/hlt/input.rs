@@ -1,59 +1,74 @@
-use hlt::log::Log;
-use std::cell::RefCell;
-use std::io::stdin;
-use std::process::exit;
-use std::rc::Rc;
+use hlt::error::{Error, Result};
+use std::any::type_name;
+use std::io::{stdin, BufRead, BufReader, Stdin, Write};
 use std::str::FromStr;
 
-pub struct Input {
-    log: Rc<RefCell<Log>>,
+//reads engine lines from any buffered source, stdin unless told otherwise,
+//optionally copying every line read into a transcript that can be fed back later
+pub struct Input<R: BufRead = BufReader<Stdin>> {
+    reader: R,
     tokens: Vec<String>,
     current_token: usize,
+    transcript: Option<Box<dyn Write>>,
 }
 
 impl Input {
-    pub fn new(log: &Rc<RefCell<Log>>) -> Input {
-        Input { log: log.clone(), tokens: Vec::new(), current_token: 0 }
+    pub fn new() -> Input {
+        Input::from_reader(BufReader::new(stdin()))
+    }
+}
+
+impl<R: BufRead> Input<R> {
+    pub fn from_reader(reader: R) -> Input<R> {
+        Input { reader, tokens: Vec::new(), current_token: 0, transcript: None }
+    }
+
+    pub fn record_to<W: Write + 'static>(&mut self, transcript: W) {
+        self.transcript = Some(Box::new(transcript));
     }
 
-    pub fn read_and_return_line(&mut self) -> String {
+    pub fn read_and_return_line(&mut self) -> Result<String> {
         let mut buf = String::new();
-        match stdin().read_line(&mut buf) {
-            Ok(_) => (),
-            Err(_) => {
-                self.log.borrow_mut().log("Input connection from server closed. Exiting...");
-                self.log.borrow_mut().flush();
-                exit(0);
-            },
+        if self.reader.read_line(&mut buf)? == 0 {
+            return Err(Error::UnexpectedEof);
         }
-        buf
+        //flushed per line so the transcript survives the bot getting killed mid game
+        if let Some(t) = self.transcript.as_mut() {
+            t.write_all(buf.as_bytes())?;
+            t.flush()?;
+        }
+        Ok(buf)
     }
 
-    pub fn read_and_parse_line(&mut self) {
-        let buf = self.read_and_return_line();
+    pub fn read_and_parse_line(&mut self) -> Result<()> {
+        let buf = self.read_and_return_line()?;
         let token_iter = buf.split_whitespace();
         let token_iter = token_iter.filter(|x| !x.is_empty());
         let token_iter = token_iter.map(|x| x.to_string());
         self.tokens = token_iter.collect();
         self.current_token = 0;
+        Ok(())
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.tokens.len() - self.current_token
     }
 
-    pub fn next_i32(&mut self) -> i32 {
+    pub fn next_i32(&mut self) -> Result<i32> {
         self.next()
     }
 
-    pub fn next_usize(&mut self) -> usize {
+    pub fn next_usize(&mut self) -> Result<usize> {
         self.next()
     }
 
-    pub fn next<T: FromStr>(&mut self) -> T {
-        let token = &self.tokens[self.current_token];
+    pub fn next<T: FromStr>(&mut self) -> Result<T> {
+        let token = match self.tokens.get(self.current_token) {
+            Some(x) => x,
+            None => return Err(Error::TruncatedFrame(format!("token {} of line '{}'", self.current_token, self.tokens.join(" ")))),
+        };
         self.current_token += 1;
 
-        let result = match token.parse() {
-            Ok(x) => x,
-            Err(_) => self.log.borrow_mut().panic(&format!("Can't parse '{}'.", token)),
-        };
-        result
+        token.parse().map_err(|_| Error::Parse { token: token.clone(), expected: type_name::<T>().to_string() })
     }
 }
@@ -1,3 +1,7 @@
 pub mod input;
 pub mod log;
 pub mod constants;
+pub mod protocol;
+pub mod error;
+pub mod command;
+pub mod validate;
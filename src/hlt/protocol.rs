@@ -0,0 +1,358 @@
+use hlt::constants::Constants;
+use hlt::error::{Error, Result};
+use hlt::input::Input;
+use std::io::BufRead;
+
+//typed view of the engine protocol, coordinates are stored as (y,x) like the maps
+
+#[derive(Clone,Copy,Debug)]
+pub struct ShipyardFrame {
+    pub player_id: usize,
+    pub pos: (i32,i32), //(y,x)
+}
+
+#[derive(Clone)]
+pub struct InitFrame {
+    pub constants: Constants,
+    pub num_players: usize,
+    pub my_id: usize,
+    pub shipyards: Vec<ShipyardFrame>,
+    pub dim: (i32,i32), //num rows, num columns
+    pub halite: Vec<Vec<usize>>,
+}
+
+#[derive(Clone,Copy,Debug)]
+pub struct ShipFrame {
+    pub id: usize,
+    pub pos: (i32,i32), //(y,x)
+    pub halite: usize,
+}
+
+#[derive(Clone,Copy,Debug)]
+pub struct DropoffFrame {
+    pub id: usize,
+    pub pos: (i32,i32), //(y,x)
+}
+
+#[derive(Clone,Debug)]
+pub struct PlayerFrame {
+    pub player_id: usize,
+    pub halite: usize,
+    pub ships: Vec<ShipFrame>,
+    pub dropoffs: Vec<DropoffFrame>,
+}
+
+#[derive(Clone,Copy,Debug)]
+pub struct MapUpdate {
+    pub pos: (i32,i32), //(y,x)
+    pub halite: usize,
+}
+
+#[derive(Clone,Debug)]
+pub struct TurnFrame {
+    pub turn_num: usize,
+    pub players: Vec<PlayerFrame>,
+    pub map_updates: Vec<MapUpdate>,
+}
+
+impl InitFrame {
+
+    //inputs:
+    //
+    //constants
+    //number of players
+    //my id
+    //for each player:
+    //  player id, shipyard x, shipyard y
+    //map width, map height
+    //resource map of halite:
+    //  [0][0] [0][1]... (1st row)
+    //  [1][0] [1][1]...
+    //  ...
+    pub fn parse<R: BufRead>( input: & mut Input<R> ) -> Result<InitFrame> {
+
+        let line = input.read_and_return_line()?;
+        let constants = Constants::new( &line )?;
+
+        input.read_and_parse_line()?;
+        let num_players : usize = input.next()?;
+        let my_id : usize = input.next()?;
+        if num_players == 0 {
+            return Err(Error::InvalidFrame("engine reported zero players".to_string()));
+        }
+        if my_id >= num_players {
+            return Err(Error::InvalidFrame(format!("my id {} out of range for {} players", my_id, num_players)));
+        }
+
+        let mut shipyards = vec![];
+        for _ in 0..num_players {
+            input.read_and_parse_line()?;
+            let player_id : usize = input.next()?;
+            let x : i32 = input.next()?;
+            let y : i32 = input.next()?;
+            if player_id >= num_players {
+                return Err(Error::InvalidFrame(format!("shipyard player id {} out of range for {} players", player_id, num_players)));
+            }
+            if shipyards.iter().any(|s: &ShipyardFrame| s.player_id == player_id ) {
+                return Err(Error::InvalidFrame(format!("duplicate shipyard for player {}", player_id)));
+            }
+            shipyards.push( ShipyardFrame { player_id, pos: (y,x) } );
+        }
+
+        input.read_and_parse_line()?;
+        let map_w : i32 = input.next()?;
+        let map_h : i32 = input.next()?;
+        if map_w <= 0 || map_h <= 0 {
+            return Err(Error::InvalidFrame(format!("invalid map size {}x{}", map_w, map_h)));
+        }
+        let dim = (map_h, map_w);
+
+        if let Some(msg) = shipyards.iter().filter_map(|s| check_pos( s.pos, dim, "shipyard" ) ).next() {
+            return Err(Error::InvalidFrame(msg));
+        }
+
+        let mut halite = Vec::with_capacity( map_h as usize );
+        for _ in 0..map_h {
+            input.read_and_parse_line()?;
+            if input.remaining() < map_w as usize {
+                return Err(Error::TruncatedFrame(format!("map row, expected {} halite values, got {}", map_w, input.remaining())));
+            }
+            if input.remaining() > map_w as usize {
+                return Err(Error::InvalidFrame(format!("expected {} halite values in map row, got {}", map_w, input.remaining())));
+            }
+            let row = (0..map_w).map(|_| input.next::<usize>() ).collect::<Result<Vec<_>>>()?;
+            halite.push( row );
+        }
+
+        Ok(InitFrame {
+            constants,
+            num_players,
+            my_id,
+            shipyards,
+            dim,
+            halite,
+        })
+    }
+}
+
+impl TurnFrame {
+
+    //input:
+    //
+    //turn num
+    //for each player:
+    //  player_id  num_ships num_dropoffs halite_amount
+    //  ship_id1 coord_x coord_y halite_value
+    //  ship_id2 coord_x coord_y halite_value
+    //  ..
+    //  (num_ships)
+    //  dropoff_id1 coord_x coord_y
+    //  dropoff_id2 coord_x coord_y
+    //  ..
+    //  (num_dropoffs)
+    //
+    //map_update_count
+    //  coord_x coord_y halite_value
+    //  ..
+    //
+    //the frame is always read to its end before an inconsistent value is reported as
+    //InvalidFrame, so the next frame can still be parsed. any other error leaves the stream
+    //somewhere inside the frame
+    pub fn parse<R: BufRead>( input: & mut Input<R>, init: &InitFrame ) -> Result<TurnFrame> {
+
+        let dim = init.dim;
+        let num_cells = (dim.0 * dim.1) as usize;
+        let mut invalid = None;
+
+        input.read_and_parse_line()?;
+        let turn_num : usize = input.next()?;
+
+        let mut players = Vec::with_capacity( init.num_players );
+        for _ in 0..init.num_players {
+            input.read_and_parse_line()?;
+            let player_id : usize = input.next()?;
+            let num_ships : usize = input.next()?;
+            let num_dropoffs : usize = input.next()?;
+            let halite : usize = input.next()?;
+
+            //counts that large can't be right, there is no telling where the frame ends
+            if num_ships > num_cells || num_dropoffs > num_cells {
+                return Err(Error::Desync(format!("turn {}: player {} reports {} ships and {} dropoffs on a {} cell map", turn_num, player_id, num_ships, num_dropoffs, num_cells)));
+            }
+            if player_id >= init.num_players {
+                invalid = invalid.or( Some( format!("turn {}: player id {} out of range for {} players", turn_num, player_id, init.num_players) ) );
+            }
+            if players.iter().any(|p: &PlayerFrame| p.player_id == player_id ) {
+                invalid = invalid.or( Some( format!("turn {}: duplicate block for player {}", turn_num, player_id) ) );
+            }
+
+            let mut ships = Vec::with_capacity( num_ships );
+            for _ in 0..num_ships {
+                input.read_and_parse_line()?;
+                let id : usize = input.next()?;
+                let x : i32 = input.next()?;
+                let y : i32 = input.next()?;
+                let ship_halite : usize = input.next()?;
+                invalid = invalid.or( check_pos( (y,x), dim, "ship" ) );
+                ships.push( ShipFrame { id, pos: (y,x), halite: ship_halite } );
+            }
+
+            let mut dropoffs = Vec::with_capacity( num_dropoffs );
+            for _ in 0..num_dropoffs {
+                input.read_and_parse_line()?;
+                let id : usize = input.next()?;
+                let x : i32 = input.next()?;
+                let y : i32 = input.next()?;
+                invalid = invalid.or( check_pos( (y,x), dim, "dropoff" ) );
+                dropoffs.push( DropoffFrame { id, pos: (y,x) } );
+            }
+
+            players.push( PlayerFrame { player_id, halite, ships, dropoffs } );
+        }
+
+        input.read_and_parse_line()?;
+        let map_update_count : usize = input.next()?;
+        if map_update_count > num_cells {
+            return Err(Error::Desync(format!("turn {}: {} map updates on a {} cell map", turn_num, map_update_count, num_cells)));
+        }
+        let mut map_updates = Vec::with_capacity( map_update_count );
+        for _ in 0..map_update_count {
+            input.read_and_parse_line()?;
+            let x : i32 = input.next()?;
+            let y : i32 = input.next()?;
+            let halite : usize = input.next()?;
+            invalid = invalid.or( check_pos( (y,x), dim, "map update" ) );
+            map_updates.push( MapUpdate { pos: (y,x), halite } );
+        }
+
+        if let Some(msg) = invalid {
+            return Err(Error::InvalidFrame(msg));
+        }
+
+        Ok(TurnFrame {
+            turn_num,
+            players,
+            map_updates,
+        })
+    }
+}
+
+//what is wrong with the position, None when it is on the map
+fn check_pos( pos: (i32,i32), dim: (i32,i32), what: &str ) -> Option<String> {
+    if pos.0 < 0 || pos.0 >= dim.0 || pos.1 < 0 || pos.1 >= dim.1 {
+        return Some(format!("{} at (y: {}, x: {}) outside of {}x{} map", what, pos.0, pos.1, dim.1, dim.0));
+    }
+    None
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    pub const CONSTANTS: &str = r#"{"DROPOFF_COST": 4000, "EXTRACT_RATIO": 4, "INITIAL_ENERGY": 5000, "INSPIRATION_ENABLED": true, "INSPIRATION_RADIUS": 4, "INSPIRATION_SHIP_COUNT": 2, "INSPIRED_BONUS_MULTIPLIER": 2.0, "INSPIRED_EXTRACT_RATIO": 4, "INSPIRED_MOVE_COST_RATIO": 10, "MAX_ENERGY": 1000, "MAX_TURNS": 10, "MOVE_COST_RATIO": 10, "NEW_ENTITY_ENERGY_COST": 1000}"#;
+
+    //init frame of a two player game on an 8x8 map, player 0's shipyard at (y: 2, x: 1)
+    pub fn init_text( my_id: usize ) -> String {
+        let mut s = format!("{}\n2 {}\n0 1 2\n1 6 5\n8 8\n", CONSTANTS, my_id );
+        for y in 0..8 {
+            s.push_str( &(0..8).map(|x| ( 10 * y + x ).to_string() ).collect::<Vec<_>>().join(" ") );
+            s.push('\n');
+        }
+        s
+    }
+
+    //turn frame with ship 0 of player 0 at (y: 3, x: 1) and one map update at (y: 7, x: 4)
+    pub fn turn_text( turn: usize ) -> String {
+        format!("{}\n0 1 0 4000\n0 1 3 25\n1 0 0 5000\n1\n4 7 12\n", turn )
+    }
+
+    fn input( s: &str ) -> Input<Cursor<Vec<u8>>> {
+        Input::from_reader( Cursor::new( s.as_bytes().to_vec() ) )
+    }
+
+    #[test]
+    fn parses_init_and_turn_frames() {
+        let mut i = input( &( init_text( 1 ) + &turn_text( 1 ) ) );
+        let init = InitFrame::parse( & mut i ).unwrap();
+        assert_eq!( init.num_players, 2 );
+        assert_eq!( init.my_id, 1 );
+        assert_eq!( init.dim, (8,8) );
+        assert_eq!( init.shipyards[0].pos, (2,1) );
+        assert_eq!( init.shipyards[1].pos, (5,6) );
+        assert_eq!( init.halite[3][5], 35 );
+        assert_eq!( init.constants.max_turns, 10 );
+
+        let frame = TurnFrame::parse( & mut i, &init ).unwrap();
+        assert_eq!( frame.turn_num, 1 );
+        assert_eq!( frame.players[0].halite, 4000 );
+        assert_eq!( frame.players[0].ships[0].pos, (3,1) );
+        assert_eq!( frame.players[0].ships[0].halite, 25 );
+        assert!( frame.players[1].ships.is_empty() );
+        assert_eq!( frame.map_updates[0].pos, (7,4) );
+        assert_eq!( frame.map_updates[0].halite, 12 );
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let mut i = input( &( init_text( 0 ) + "1\n0 1 0 4000\n0 1 3\n" ) );
+        let init = InitFrame::parse( & mut i ).unwrap();
+        match TurnFrame::parse( & mut i, &init ) {
+            Err(Error::TruncatedFrame(_)) => {},
+            x => panic!("expected a truncated frame, got {:?}", x.map(|f| f.turn_num ) ),
+        }
+    }
+
+    #[test]
+    fn truncated_map_row() {
+        let text = init_text( 0 ).replacen( "0 1 2 3 4 5 6 7\n", "0 1 2 3 4 5 6\n", 1 );
+        match InitFrame::parse( & mut input( &text ) ) {
+            Err(Error::TruncatedFrame(_)) => {},
+            x => panic!("expected a truncated frame, got {:?}", x.map(|f| f.dim ) ),
+        }
+    }
+
+    #[test]
+    fn bad_token() {
+        let mut i = input( &( init_text( 0 ) + "1\n0 1 0 4000\n0 one 3 25\n" ) );
+        let init = InitFrame::parse( & mut i ).unwrap();
+        match TurnFrame::parse( & mut i, &init ) {
+            Err(Error::Parse { token, .. }) => assert_eq!( token, "one" ),
+            x => panic!("expected a parse error, got {:?}", x.map(|f| f.turn_num ) ),
+        }
+    }
+
+    #[test]
+    fn out_of_range_position() {
+        let mut i = input( &( init_text( 0 ) + "1\n0 1 0 4000\n0 8 3 25\n1 0 0 5000\n0\n" ) );
+        let init = InitFrame::parse( & mut i ).unwrap();
+        match TurnFrame::parse( & mut i, &init ) {
+            Err(Error::InvalidFrame(_)) => {},
+            x => panic!("expected an invalid frame, got {:?}", x.map(|f| f.turn_num ) ),
+        }
+    }
+
+    #[test]
+    fn invalid_frame_is_read_to_its_end() {
+        let mut i = input( &( init_text( 0 ) + "1\n0 1 0 4000\n0 8 3 25\n1 0 0 5000\n1\n4 9 12\n" + &turn_text( 2 ) ) );
+        let init = InitFrame::parse( & mut i ).unwrap();
+        assert!( TurnFrame::parse( & mut i, &init ).is_err() );
+        let frame = TurnFrame::parse( & mut i, &init ).unwrap();
+        assert_eq!( frame.turn_num, 2 );
+        assert_eq!( frame.players[0].ships[0].pos, (3,1) );
+    }
+
+    #[test]
+    fn eof() {
+        match InitFrame::parse( & mut input("") ) {
+            Err(Error::UnexpectedEof) => {},
+            x => panic!("expected eof, got {:?}", x.map(|f| f.dim ) ),
+        }
+        let mut i = input( &init_text( 0 ) );
+        let init = InitFrame::parse( & mut i ).unwrap();
+        match TurnFrame::parse( & mut i, &init ) {
+            Err(Error::UnexpectedEof) => {},
+            x => panic!("expected eof, got {:?}", x.map(|f| f.turn_num ) ),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use hlt::command::Command;
+use hlt::log::Log;
+use hlt::protocol::{InitFrame, ShipFrame, TurnFrame};
+use mapping::gamemap::GameMap;
+use metric::torus;
+use std::collections::{HashMap, HashSet};
+
+//checks the turn's commands against the parsed state before they go to the engine,
+//invalid commands are dropped or turned into a stay and the reason is logged
+pub fn validate( log: & mut Log, commands: Vec<Command>, init: &InitFrame, frame: &TurnFrame, map: &GameMap ) -> Vec<Command> {
+
+    let constants = &init.constants;
+
+    let me = match frame.players.iter().find(|p| p.player_id == init.my_id ) {
+        Some(x) => x,
+        None => {
+            log.log(&format!("validate: turn {}: no player block for my id {}, dropping all {} commands", frame.turn_num, init.my_id, commands.len()));
+            return vec![];
+        },
+    };
+
+    let my_ships : HashMap<usize,&ShipFrame> = me.ships.iter().map(|s| (s.id, s) ).collect();
+
+    let structures : HashSet<(i32,i32)> = init.shipyards.iter().map(|s| s.pos )
+        .chain( frame.players.iter().flat_map(|p| p.dropoffs.iter().map(|d| d.pos ) ) )
+        .collect();
+
+    let mut budget = me.halite;
+    let mut commanded = HashSet::new();
+    let mut leaving = HashSet::new();
+    let mut arriving = HashMap::new();
+    let mut spawn_requested = false;
+    let mut ret = Vec::with_capacity( commands.len() );
+
+    for cmd in commands {
+
+        let id = match cmd.ship_id() {
+            Some(x) => x,
+            None => {
+                if spawn_requested {
+                    log.log(&format!("validate: turn {}: dropping duplicate spawn", frame.turn_num));
+                } else {
+                    spawn_requested = true;
+                }
+                continue;
+            },
+        };
+
+        let ship = match my_ships.get(&id) {
+            Some(x) => x,
+            None => {
+                log.log(&format!("validate: turn {}: dropping {}, ship {} is not mine", frame.turn_num, cmd, id));
+                continue;
+            },
+        };
+
+        if !commanded.insert(id) {
+            log.log(&format!("validate: turn {}: dropping {}, ship {} already has a command", frame.turn_num, cmd, id));
+            continue;
+        }
+
+        let cell_halite = map.halite( map.pos( ship.pos.0, ship.pos.1 ) );
+
+        match cmd {
+            Command::Move(_,dir) => {
+                let move_cost = cell_halite / constants.move_cost_ratio;
+                if ship.halite < move_cost {
+                    log.log(&format!("validate: turn {}: {} needs {} halite to move, ship has {}, staying instead", frame.turn_num, cmd, move_cost, ship.halite));
+                    ret.push( Command::Stay(id) );
+                } else {
+                    let delta = dir.delta();
+                    let to = torus::wrap( ( ship.pos.0 + delta.0, ship.pos.1 + delta.1 ), init.dim );
+                    leaving.insert( ship.pos );
+                    arriving.insert( to, id );
+                    ret.push( cmd );
+                }
+            },
+            Command::ConvertToDropoff(_) => {
+                let cost = constants.dropoff_cost.saturating_sub( ship.halite + cell_halite );
+                if structures.contains( &ship.pos ) {
+                    log.log(&format!("validate: turn {}: {} is on an existing structure, staying instead", frame.turn_num, cmd));
+                    ret.push( Command::Stay(id) );
+                } else if budget < cost {
+                    log.log(&format!("validate: turn {}: {} costs {} halite, have {}, staying instead", frame.turn_num, cmd, cost, budget));
+                    ret.push( Command::Stay(id) );
+                } else {
+                    budget -= cost;
+                    ret.push( cmd );
+                }
+            },
+            _ => {
+                ret.push( cmd );
+            },
+        }
+    }
+
+    if spawn_requested {
+        let shipyard = init.shipyards.iter().find(|s| s.player_id == init.my_id ).map(|s| s.pos );
+        let blocked_by = shipyard.and_then(|pos| {
+            frame.players.iter()
+                .flat_map(|p| p.ships.iter().map(move |s| (p.player_id, s) ) )
+                .find(|(player_id,s)| s.pos == pos && !( *player_id == init.my_id && leaving.contains( &s.pos ) ) )
+                .map(|(_,s)| s.id )
+                .or_else(|| arriving.get( &pos ).cloned() )
+        });
+        if shipyard.is_none() {
+            log.log(&format!("validate: turn {}: dropping spawn, no shipyard known for my id {}", frame.turn_num, init.my_id));
+        } else if budget < constants.ship_cost {
+            log.log(&format!("validate: turn {}: dropping spawn, costs {} halite, have {}", frame.turn_num, constants.ship_cost, budget));
+        } else if let Some(ship_id) = blocked_by {
+            log.log(&format!("validate: turn {}: dropping spawn, ship {} occupies the shipyard", frame.turn_num, ship_id));
+        } else {
+            ret.push( Command::Spawn );
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use hlt::command::Direction;
+    use hlt::input::Input;
+    use hlt::protocol::tests::init_text;
+    use hlt::protocol::{DropoffFrame, PlayerFrame};
+    use std::io::Cursor;
+
+    //player 0 on the 8x8 map of init_text, shipyard at (2,1), cell (y,x) holds 10y+x halite;
+    //player 1's shipyard is at (5,6) and its ship 7 sits at (6,6)
+    fn check( commands: Vec<Command>, halite: usize, ships: &[(usize,(i32,i32),usize)], dropoffs: &[(usize,(i32,i32))] ) -> Vec<Command> {
+        let init = InitFrame::parse( & mut Input::from_reader( Cursor::new( init_text( 0 ).into_bytes() ) ) ).unwrap();
+        let frame = TurnFrame {
+            turn_num: 1,
+            players: vec![
+                PlayerFrame {
+                    player_id: 0,
+                    halite,
+                    ships: ships.iter().map(|&(id,pos,halite)| ShipFrame { id, pos, halite } ).collect(),
+                    dropoffs: vec![],
+                },
+                PlayerFrame {
+                    player_id: 1,
+                    halite: 5000,
+                    ships: vec![ ShipFrame { id: 7, pos: (6,6), halite: 0 } ],
+                    dropoffs: dropoffs.iter().map(|&(id,pos)| DropoffFrame { id, pos } ).collect(),
+                },
+            ],
+            map_updates: vec![],
+        };
+        let mut map = GameMap::from_halite( &init.halite );
+        map.apply_frame( &frame );
+        validate( & mut Log::new(), commands, &init, &frame, &map )
+    }
+
+    #[test]
+    fn second_command_for_a_ship_is_dropped() {
+        let ret = check( vec![ Command::Move(0,Direction::North), Command::Stay(0) ], 5000, &[ (0,(3,1),25) ], &[] );
+        assert_eq!( ret, vec![ Command::Move(0,Direction::North) ] );
+    }
+
+    #[test]
+    fn unaffordable_move_becomes_a_stay() {
+        //leaving (7,7) costs 7
+        let ret = check( vec![ Command::Move(0,Direction::West) ], 5000, &[ (0,(7,7),6) ], &[] );
+        assert_eq!( ret, vec![ Command::Stay(0) ] );
+        let ret = check( vec![ Command::Move(0,Direction::West) ], 5000, &[ (0,(7,7),7) ], &[] );
+        assert_eq!( ret, vec![ Command::Move(0,Direction::West) ] );
+    }
+
+    #[test]
+    fn spawn_needs_the_ship_cost() {
+        assert!( check( vec![ Command::Spawn ], 999, &[], &[] ).is_empty() );
+        assert_eq!( check( vec![ Command::Spawn, Command::Spawn ], 1000, &[], &[] ), vec![ Command::Spawn ] );
+    }
+
+    #[test]
+    fn spawn_onto_an_occupied_shipyard_is_dropped() {
+        //a ship staying on it, a ship moving onto it
+        assert_eq!( check( vec![ Command::Stay(0), Command::Spawn ], 5000, &[ (0,(2,1),0) ], &[] ), vec![ Command::Stay(0) ] );
+        assert_eq!( check( vec![ Command::Spawn ], 5000, &[ (0,(2,1),0) ], &[] ), vec![] );
+        assert_eq!( check( vec![ Command::Move(0,Direction::North), Command::Spawn ], 5000, &[ (0,(3,1),25) ], &[] ), vec![ Command::Move(0,Direction::North) ] );
+    }
+
+    #[test]
+    fn spawn_is_allowed_when_the_ship_on_the_shipyard_moves_off() {
+        let ret = check( vec![ Command::Spawn, Command::Move(0,Direction::East) ], 5000, &[ (0,(2,1),25) ], &[] );
+        assert_eq!( ret, vec![ Command::Move(0,Direction::East), Command::Spawn ] );
+    }
+
+    #[test]
+    fn conversion_on_a_structure_becomes_a_stay() {
+        assert_eq!( check( vec![ Command::ConvertToDropoff(0) ], 5000, &[ (0,(2,1),0) ], &[] ), vec![ Command::Stay(0) ] );
+        assert_eq!( check( vec![ Command::ConvertToDropoff(0) ], 5000, &[ (0,(4,4),0) ], &[ (3,(4,4)) ] ), vec![ Command::Stay(0) ] );
+    }
+
+    #[test]
+    fn conversion_the_budget_cant_cover_becomes_a_stay() {
+        //at (3,1) the dropoff costs 4000 - 31 of the cell - 69 of cargo = 3900
+        assert_eq!( check( vec![ Command::ConvertToDropoff(0) ], 3899, &[ (0,(3,1),69) ], &[] ), vec![ Command::Stay(0) ] );
+        assert_eq!( check( vec![ Command::ConvertToDropoff(0) ], 3900, &[ (0,(3,1),69) ], &[] ), vec![ Command::ConvertToDropoff(0) ] );
+        //the spawn is paid from what the conversion leaves
+        assert_eq!( check( vec![ Command::ConvertToDropoff(0), Command::Spawn ], 4899, &[ (0,(3,1),69) ], &[] ), vec![ Command::ConvertToDropoff(0) ] );
+        assert_eq!( check( vec![ Command::ConvertToDropoff(0), Command::Spawn ], 4900, &[ (0,(3,1),69) ], &[] ), vec![ Command::ConvertToDropoff(0), Command::Spawn ] );
+    }
+
+    #[test]
+    fn commands_for_other_ships_are_dropped() {
+        assert!( check( vec![ Command::Move(7,Direction::North), Command::Stay(3) ], 5000, &[ (0,(3,1),25) ], &[] ).is_empty() );
+    }
+}
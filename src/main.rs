@@ -1,22 +1,42 @@
 extern crate rand;
+extern crate ruzstd;
+#[macro_use]
+extern crate serde_json;
 
 mod hlt;
 mod mapping;
 mod metric;
+mod movement;
+mod render;
+mod replay;
+mod sim;
 
+use hlt::command::{Command,Direction};
 use mapping::{mapraw};
+use mapping::gamemap::GameMap;
+use mapping::halitesum::HaliteSum;
+use mapping::inspiration::InspirationMap;
+use mapping::distfield::DistanceField;
+use mapping::threat::ThreatMap;
+use mapping::history::HaliteHistory;
+use metric::torus;
+use movement::astar::{self, PathCosts};
+use movement::{congestion, cooperative, resolver};
+use movement::congestion::Congestion;
 
 use rand::Rng;
 use rand::distributions::{Distribution,Uniform};
 use rand::SeedableRng;
 use rand::XorShiftRng;
 use std::env;
+use std::io;
+use std::io::{BufRead,BufReader,Read,Write};
+use std::fs::File;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 use std::time::{Duration,Instant};
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::ops::DerefMut;
 use std::collections::{HashMap,HashSet};
 use std::ops::{Add,Sub};
 
@@ -60,33 +80,93 @@ enum TaskShortTerm {
     Stationary,
 }
 
-fn schedule( queued: Vec<(usize,Coord,Coord)>, map_r: &mapraw::ResourceMap, map_d: &mapraw::DropoffMap, map_u: & mut mapraw::UnitMap, is_end_game: &bool, my_id: &usize ) -> Vec<(usize,Dir)> {
+//cargo from which a ship avoids cells an enemy is likely to move into
+const LADEN_HALITE: usize = 300;
+const MAX_THREAT_LADEN: f64 = 0.3;
 
-    let map_dim = map_r.dim;
+//ships this close to one of my structures plan cooperatively over the next turns
+const COOP_RADIUS: i32 = 6;
+const COOP_WINDOW: usize = 8;
+
+fn is_laden( unit: &mapraw::Unit ) -> bool {
+    match *unit {
+        mapraw::Unit::Ship { halite, .. } => halite >= LADEN_HALITE,
+        mapraw::Unit::None => false,
+    }
+}
+
+//per turn views of the map shared by strategy and scheduling
+struct TurnLayers<'a> {
+    map: &'a GameMap,
+    halite_sum: &'a HaliteSum,
+    inspiration: InspirationMap,
+    dist_field: DistanceField,
+    path_costs: PathCosts,
+    threat: ThreatMap,
+}
+
+//moves every queued ship towards its target, the ranked next cells of all ships are assigned together.
+//every ship of mine takes part, one that stays put for whatever reason with its own cell as the
+//only option, so a cell another of my ships stands on is open as well: the assignment only lets
+//a ship in when the occupant is given a different cell, lines of ships advance together and neighbours swap
+fn schedule<R: Rng>( queued: Vec<(usize,Coord,Coord)>, scheduled: & mut GameMap, is_end_game: &bool, my_id: &usize, layers: &TurnLayers, congestion: & mut Congestion, rng: & mut R ) -> Vec<(usize,Dir)> {
+
+    let map = layers.map;
+    let map_dim = map.dim;
+    let is_my_structure = |y: i32, x: i32| map.structure( map.pos( y, x ) ).is_some_and(|p| p.0 == *my_id );
     
     let mut ret : Vec<(usize,Dir)> = vec![];
+    let mut requests = vec![];
+    //a ship that can't pay the move cost stays whatever it is told
+    let can_move = |p: (i32,i32)| match map.ship( map.pos( p.0, p.1 ) ) {
+        mapraw::Unit::Ship { halite, .. } => halite >= map.halite( map.pos( p.0, p.1 ) ) / layers.path_costs.move_cost_ratio,
+        mapraw::Unit::None => false,
+    };
+
+    //ships without a queued movement keep their cell too
+    let queued_ids = queued.iter().map(|x| x.0 ).collect::<HashSet<_>>();
+    for (id,pos) in map.ships_of( *my_id ).into_iter().filter(|x| !queued_ids.contains( &x.0 ) ) {
+        requests.push( resolver::Request { id, from: pos.yx(), candidates: vec![ (pos.yx(), 0.) ] } );
+    }
+
+    let shared : HashSet<(i32,i32)> = if *is_end_game {
+        map.structures( *my_id ).iter().map(|x| x.1.yx() ).collect()
+    } else {
+        HashSet::new()
+    };
+
+    //around my structures moving ships reserve their next turns one after the other, laden ones
+    //first, every ship staying put holds its cell
+    let structures = map.structures( *my_id ).iter().map(|x| x.1.yx() ).collect::<Vec<_>>();
+    let mut table = cooperative::ReservationTable::new( COOP_WINDOW, shared.clone() );
+    if !*is_end_game {
+        table.forbid_moves( congestion::against_lanes( &structures, map_dim ) );
+    }
+    for r in requests.iter() {
+        table.reserve_stay( r.from );
+    }
+    let queued_to = queued.iter().map(|&(id,_,to)| (id, to.0) ).collect::<HashMap<_,_>>();
+    let mut busy = vec![];
+    for &(id,from,to) in queued.iter() {
+        let origin = map.pos( (from.0).0, (from.0).1 ).yx();
+        if from == to || !can_move( origin ) {
+            table.reserve_stay( origin );
+        } else if layers.dist_field.turns( origin.0, origin.1 ) <= COOP_RADIUS {
+            busy.push( (id, origin, to.0) );
+        }
+    }
+    busy.sort_by_key(|&(id,from,_)| ( !is_laden( &map.ship( map.pos( from.0, from.1 ) ) ), id ) );
+    let routes = cooperative::plan( map, &busy, &layers.path_costs, & mut table );
     
     for (id,from,to) in queued {
 
-        if from == to {
+        let origin = map.pos( (from.0).0, (from.0).1 ).yx();
+        if from == to || !can_move( origin ) {
+            requests.push( resolver::Request { id, from: origin, candidates: vec![ (origin, 0.) ] } );
             continue;
         }
         
-        let mut dif = to-from;
-
-        if (dif.0).0 > map_r.dim.0/2 {
-            (dif.0).0 -= map_r.dim.0;
-        }
-        if (dif.0).0 < -map_r.dim.0/2 {
-            (dif.0).0 += map_r.dim.0;
-        }
-
-        if (dif.0).1 > map_r.dim.1/2 {
-            (dif.0).1 -= map_r.dim.1;
-        }
-        if (dif.0).1 < -map_r.dim.1/2 {
-            (dif.0).1 += map_r.dim.1;
-        }
+        let dif = Coord( torus::wrapped_delta( from.0, to.0, map_dim ) );
 
         let mut choices_no = vec![];
         
@@ -117,113 +197,98 @@ fn schedule( queued: Vec<(usize,Coord,Coord)>, map_r: &mapraw::ResourceMap, map_
             choices_no.push( (from, Dir((1,0))) );
         }
 
-        rand::thread_rng().shuffle( & mut choices[..] );
+        rng.shuffle( & mut choices[..] );
 
-        rand::thread_rng().shuffle( & mut choices_no[..] );
+        rng.shuffle( & mut choices_no[..] );
         choices.extend_from_slice( &choices_no[..] );
 
+        //the first step of the cheapest path goes ahead of the greedy choices
+        if let Some(next) = astar::find_path( map, from.0, to.0, &layers.path_costs ).and_then(|path| path.first().cloned() ) {
+            choices.insert( 0, (from, Dir( torus::wrapped_delta( from.0, next, map_dim ) )) );
+        }
 
-        let mut found = false;
-        let choices_filtered = choices
-            .iter()
-            .filter_map(|(fr,dir)| {
-                let y = (fr.0).0 + (dir.0).0;
-                let x = (fr.0).1 + (dir.0).1;
-                let agent = map_u.get( (fr.0).0, (fr.0).1 );
-                if let mapraw::Unit::None = agent {
-                    // panic!("ship not found at expected location: {:?}, map_u: {:?}", fr.0, map_u.invmap );
-                    //already processed, skip it
-                    None                    
-                } else {
-                    if let mapraw::Unit::None = map_u.get( y, x ) {
-                        if found {
-                            None
-                        } else {
-                            // map_u.set( (fr.0).0, (fr.0).1, mapraw::Unit::None );
-                            map_u.set( y, x, agent );
-                            found = true;
-                            Some(dir)
-                        }
-                    } else {
-                        if *is_end_game {
-                            if let Some(dropoff_player_id) = map_d.get( y, x ) {
-                                if dropoff_player_id.0 == *my_id {
-                                    map_u.set( y, x, agent );
-                                    found = true;
-                                    Some(dir)
-                                } else { None }
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
-                    }
-                }
-            })
-            .cloned()
-            .collect::<Vec<_>>();
-
-        // let choices_non_optimal_filtered = choices_non_optimal
-        //     .iter()
-        //     .filter_map(|(fr,dir)| {
-        //         let y = (fr.0).0 + (dir.0).0;
-        //         let x = (fr.0).1 + (dir.0).1;
-        //         let agent = map_u.get( (fr.0).0, (fr.0).1 );
-        //         if let mapraw::Unit::None = agent {
-        //             panic!("ship not found at expected location: {:?}, map_u: {:?}", fr.0, map_u.invmap );
-        //             //already processed, skip it
-        //             // None
-        //         } else {
-        //             if let mapraw::Unit::None = map_u.get( y, x ) {
-        //                 // map_u.set( (fr.0).0, (fr.0).1, mapraw::Unit::None );
-        //                 map_u.set( y, x, agent );
-        //                 Some(dir)    
-        //             } else {
-        //                 None
-        //             }
-        //         }
-        //     })
-        //     .cloned()
-        //     .collect::<Vec<_>>();
-            
-        if choices_filtered.is_empty() {
-            // if choices_non_optimal.is_empty() {
-            //     continue;
-            // } else {
-            //     let mut rng = rand::thread_rng();
-            //     let idx = rng.gen_range(0, choices_non_optimal_filtered.len());
-            //     ret.push( ( id, choices_non_optimal_filtered[idx] ) );
-            // }
-        } else {   
-            let mut rng = rand::thread_rng();
-            let idx = rng.gen_range(0, choices_filtered.len());
-            // map_u.set( (from.0).0, (from.0).1, mapraw::Unit::None );
-            ret.push( ( id, choices_filtered[idx] ) );
+        //the cooperative route outranks both, including when it waits a turn
+        let mut candidates : Vec<((i32,i32),f64)> = vec![];
+        match routes.get( &id ).and_then(|r| r.first().cloned() ) {
+            Some(next) if next == origin => candidates.push( (origin, 0.) ),
+            Some(next) => choices.insert( 0, (from, Dir( torus::wrapped_delta( origin, next, map_dim ) )) ),
+            None => {},
+        }
+
+        //rank the reachable cells in choice order, staying put is left as the costliest option
+        //unless the cooperative route waits
+        let agent = map.ship( map.pos( (from.0).0, (from.0).1 ) );
+        for (fr,dir) in choices.iter() {
+            let cell = map.pos( (fr.0).0 + (dir.0).0, (fr.0).1 + (dir.0).1 ).yx();
+            if candidates.iter().any(|c| c.0 == cell ) {
+                continue;
+            }
+            if is_laden( &agent ) && layers.threat.probability( cell.0, cell.1 ) > MAX_THREAT_LADEN && !is_my_structure( cell.0, cell.1 ) {
+                //too likely to get rammed next turn with this much cargo
+                continue;
+            }
+            //in the end game ships may pile onto an own structure, the cargo is deposited anyway
+            let free = match map.ship( map.pos( cell.0, cell.1 ) ) {
+                mapraw::Unit::None => true,
+                mapraw::Unit::Ship { player, .. } if player == *my_id => true,
+                _ => *is_end_game && is_my_structure( cell.0, cell.1 ),
+            };
+            if free {
+                candidates.push( (cell, candidates.len() as f64) );
+            }
         }
+
+        requests.push( resolver::Request { id, from: origin, candidates } );
+    }
+
+    //traffic rules next to my structures, except in the end game when ships pile in
+    let wants_move = requests.iter().filter(|r| r.candidates.iter().any(|c| c.0 != r.from ) ).map(|r| r.id ).collect::<HashSet<_>>();
+    if !*is_end_game {
+        let returning = requests.iter()
+            .filter(|r| is_laden( &map.ship( map.pos( r.from.0, r.from.1 ) ) ) )
+            .filter(|r| queued_to.get( &r.id ).is_some_and(|to| is_my_structure( to.0, to.1 ) ) )
+            .map(|r| r.id )
+            .collect::<HashSet<_>>();
+        //a gridlocked ship gets the same checks as the cells it ranked itself
+        let can_enter = |from: (i32,i32), cell: (i32,i32)| {
+            let risky = is_laden( &map.ship( map.pos( from.0, from.1 ) ) ) && layers.threat.probability( cell.0, cell.1 ) > MAX_THREAT_LADEN && !is_my_structure( cell.0, cell.1 );
+            !risky && !layers.path_costs.blocked.contains( &cell )
+        };
+        congestion.regulate( & mut requests, &structures, &returning, &wants_move, can_enter, map_dim );
+    }
+
+    let resolved = resolver::resolve( &requests, &shared );
+    congestion.observe( &requests, &resolved, &wants_move, &structures, map_dim );
+
+    let moves = resolved.into_iter()
+        .zip( requests.iter() )
+        .filter(|((_,cell),r)| *cell != r.from )
+        .map(|((id,cell),r)| (id, map.pos( r.from.0, r.from.1 ), map.pos( cell.0, cell.1 )) )
+        .collect::<Vec<_>>();
+
+    //vacate every origin before claiming the destinations, a destination may be another mover's origin
+    for &(_,from,_) in moves.iter() {
+        scheduled.remove_ship( from );
+    }
+    for &(id,from,cell) in moves.iter() {
+        scheduled.set_ship( cell, map.ship( from ) );
+        ret.push( ( id, Dir( torus::wrapped_delta( from.yx(), cell.yx(), map_dim ) ) ) );
     }
     
     ret
 }
 
-fn add_movement_cmd( shipid: &usize, dir: &Dir, cmd: & mut Vec<String> ) -> Result< (), & 'static str > {
+fn add_movement_cmd( shipid: &usize, dir: &Dir, cmd: & mut Vec<Command> ) -> Result< (), & 'static str > {
     if (dir.0).0.abs() + (dir.0).1.abs() > 1 {
         Err( "Dir value invalid" )
     } else {
-        match dir {
-            &Dir((-1,0)) => {
-                cmd.push( format!("m {} n", shipid ) );
-            },
-            &Dir((1,0)) => {
-                cmd.push( format!("m {} s", shipid ) );
+        match Direction::from_delta( dir.0 ) {
+            Some(d) => {
+                cmd.push( Command::Move( *shipid, d ) );
             },
-            &Dir((0,-1)) => {
-                cmd.push( format!("m {} w", shipid ) );
+            None => {
+                cmd.push( Command::Stay( *shipid ) );
             },
-            &Dir((0,1)) => {
-                cmd.push( format!("m {} e", shipid ) );
-            },
-            _ => {},
         }
         Ok( () )
     }
@@ -308,22 +373,20 @@ impl Agent {
         self.assigned_dropoff = Some(pos);
     }
     //return current pos and desired destination
-    fn execute( & mut self, map_r: &mapraw::ResourceMap, log: & mut hlt::log::Log ) -> (usize,Coord,Coord) {
+    fn execute<R: Rng>( & mut self, map: &GameMap, log: & mut hlt::log::Log, rng: & mut R ) -> (usize,Coord,Coord) {
             
         log.log(&format!("agent execute: {:?}", self));
 
         match self.status {
             AgentStatus::Idle => {},
             AgentStatus::Mining => {
-                let mine_resource = map_r.get( (self.pos.0).0, (self.pos.0).1 );
+                let mine_resource = map.halite( map.pos( (self.pos.0).0, (self.pos.0).1 ) );
                 if self.halite >= 850 && self.cooldown_mine() <= 0 {
-                    let mut rng = rand::thread_rng();
                     let num_gen: f32 = rng.gen();
                     if num_gen < 0.3 || self.halite >= 950 {
                         self.status = AgentStatus::MoveToDropoff;
                     }
                 } else if self.cooldown_mine() <= 0 && mine_resource < 50 {
-                    let mut rng = rand::thread_rng();
                     let num_gen: f32 = rng.gen();
                     if num_gen < 0.95 {
                         self.status = AgentStatus::MoveToDropoff;
@@ -376,56 +439,67 @@ impl Agent {
     }
 }
 
-fn synchronize_player_agents( player_agents: & HashMap<usize,Agent>, update: Vec<(usize,(i32,i32),usize)> ) -> ( HashMap<usize,Agent>, Vec<Agent> ) {
-    let mut processed_ids = HashSet::new();
-    let mut ret = HashMap::new();
+//applies the turn's map events of one player to its agents, returns the agents whose ship is gone
+//events of a ship without an agent are logged and skipped, the agent is made when the ship spawns
+fn synchronize_player_agents( log: & mut hlt::log::Log, player_agents: & mut HashMap<usize,Agent>, player_id: usize, events: &[mapraw::MapEvent] ) -> Vec<Agent> {
 
-    let update_ids = update.iter().map(|x| x.0).collect::<HashSet<_>>();
-    
-    for (id,(y,x),halite) in update {
-        processed_ids.insert(id);
-        match player_agents.get(&id) {
-            Some(a) => {
-                assert_eq!(id, a.id);
-                let mut agent_updated = a.clone();
-                agent_updated.halite = halite;
-                agent_updated.pos = Coord( (y,x) );
-                ret.insert(id, agent_updated);
-                // assert_eq!( agent_updated.expected_next_pos, Coord((y,x)) );
-            },
-            _ => {
+    let mut removed_agents = vec![];
+
+    for e in events {
+        match *e {
+            mapraw::MapEvent::ShipSpawned { player, id, pos, halite } if player == player_id => {
                 //new agent found
                 let a = Agent {
                     assigned_mine: None,
                     assigned_dropoff: None,
                     status: AgentStatus::Idle,
-                    halite: halite,
-                    pos: Coord( (y,x) ),
-                    id: id,
+                    halite,
+                    pos: Coord( pos ),
+                    id,
                     cooldown_mine: 0i32,
                     cooldown_movetomine: 0i32,
                 };
-                ret.insert(id, a);
+                player_agents.insert(id, a);
+            },
+            mapraw::MapEvent::ShipMoved { player, id, to, .. } if player == player_id => {
+                match player_agents.get_mut(&id) {
+                    Some(a) => { a.pos = Coord( to ); },
+                    None => { log.log(&format!("Error: no agent for {}, ignoring it", e)); },
+                }
+            },
+            mapraw::MapEvent::CargoChanged { player, id, to, .. } if player == player_id => {
+                match player_agents.get_mut(&id) {
+                    Some(a) => { a.halite = to; },
+                    None => { log.log(&format!("Error: no agent for {}, ignoring it", e)); },
+                }
             },
+            mapraw::MapEvent::ShipDestroyed { player, id, .. } if player == player_id => {
+                if let Some(a) = player_agents.remove(&id) {
+                    removed_agents.push( a );
+                }
+            },
+            _ => {},
         }
     }
 
-    let mut removed_agents = vec![];
-    
-    for i in player_agents.keys().cloned().collect::<HashSet<_>>().difference( &update_ids ) {
-        let a = player_agents.get(&i).expect("agent not found");
-        removed_agents.push( a.clone() );
-    }
-        
-    ( ret, removed_agents )
+    removed_agents
 }
 
-fn plan_strategy( log: & mut hlt::log::Log, myid: &usize, player_agents: & mut HashMap<usize,Agent>, map_r: &mapraw::ResourceMap, map_d: &mapraw::DropoffMap, map_u: & mapraw::UnitMap ) {
+//radius of the neighbourhood that counts towards a mining cell's value
+const CLUSTER_RADIUS: i32 = 2;
+
+fn plan_strategy<R: Rng>( log: & mut hlt::log::Log, myid: &usize, player_agents: & mut HashMap<usize,Agent>, layers: &TurnLayers, rng: & mut R ) {
+
+    let map = layers.map;
     
     let mut agent_action_change = vec![];
 
-    //find agents with mine resource amount below a threshold
-    for (id,a) in player_agents.iter() {
+    //find agents with mine resource amount below a threshold, in id order since hash map order
+    //differs between runs and would make the seeded rng draws differ too
+    let mut ids = player_agents.keys().cloned().collect::<Vec<_>>();
+    ids.sort();
+    for id in ids.iter() {
+        let a = &player_agents[id];
         match a.status {
             AgentStatus::Idle => {
                 agent_action_change.push(*id);
@@ -436,7 +510,7 @@ fn plan_strategy( log: & mut hlt::log::Log, myid: &usize, player_agents: & mut H
                     Some(x) => {
                         // let (y,x) = (a.pos).0;
                         if let Some(Coord((y,x))) = a.assigned_mine {
-                            let resource_count = map_r.get( y, x );
+                            let resource_count = map.halite( map.pos( y, x ) );
                             let mut assign_new_mine = false;
                             if a.cooldown_movetomine() <= 0 && a.cooldown_mine() <= 0 {
                                 assign_new_mine = true;
@@ -444,7 +518,6 @@ fn plan_strategy( log: & mut hlt::log::Log, myid: &usize, player_agents: & mut H
                             if ( resource_count < 50 && assign_new_mine )//  ||
                             // resource_count <= 50 {
                             {
-                                let mut rng = rand::thread_rng();                            
                                 let num_gen: f32 = rng.gen();
                                 if num_gen < 0.5 {
                                     agent_action_change.push(*id);
@@ -461,11 +534,11 @@ fn plan_strategy( log: & mut hlt::log::Log, myid: &usize, player_agents: & mut H
     //log.log(&format!("agent_action_change: {:?}", agent_action_change));
 
     //todo: find mining locations and assign to associated agents, update assigned dropoff locations as well
-    for (dropoff_id,dropoff_pos) in map_d.invmap.get(myid).expect("player id not found for dropoff map").iter() {
+    for &(dropoff_id,dropoff_pos) in map.structures( *myid ) {
         
         //trace out a square path and find cells that have halite amount above a threshold
         
-        let mut p = dropoff_pos;
+        let mut p = dropoff_pos.yx();
         let mut step_stop = 2;
         let mut p_n = (p.0+step_stop/2, p.1+step_stop/2);
         log.log(&format!("p_n init: {:?}", p_n));
@@ -478,7 +551,7 @@ fn plan_strategy( log: & mut hlt::log::Log, myid: &usize, player_agents: & mut H
 
         let mut cell_processed = 0;
         let mut cell_total = 0;
-        while cell_processed < agent_action_change.len() && cell_total < map_r.dim.0 * map_r.dim.1 {
+        while cell_processed < agent_action_change.len() && cell_total < map.dim.0 * map.dim.1 {
             
             if step_count >= step_stop {
                 let new_d = { match d {
@@ -505,9 +578,17 @@ fn plan_strategy( log: & mut hlt::log::Log, myid: &usize, player_agents: & mut H
             log.log(&format!("p_n: {:?}, step count: {}", p_n, step_count));
             
             step_count += 1;
-            let halite_in_cell = map_r.get( p_n.0, p_n.1 );
+            let cell_pos = map.pos( p_n.0, p_n.1 );
+            let halite_in_cell = map.halite( cell_pos );
+
+            //a cell inside a rich cluster is valued halfway towards the average of its neighbours
+            let neighbours = layers.halite_sum.diamond_sum( cell_pos.y(), cell_pos.x(), CLUSTER_RADIUS ) - halite_in_cell;
+            let neighbour_avg = neighbours / ( 2 * CLUSTER_RADIUS * ( CLUSTER_RADIUS + 1 ) ) as usize;
+            let halite_in_cell = halite_in_cell.max( ( halite_in_cell + neighbour_avg ) / 2 );
+
+            //and by what a ship would actually gain there, inspired cells pay the bonus on top
+            let halite_in_cell = ( halite_in_cell as f64 * layers.inspiration.gain_factor( *myid, cell_pos.y(), cell_pos.x() ) ) as usize;
 
-            let mut rng = rand::thread_rng();
             let num_gen_2: f32 = rng.gen();
             
             if (halite_in_cell >= 750 && num_gen_2 < 0.75 ) ||
@@ -516,11 +597,10 @@ fn plan_strategy( log: & mut hlt::log::Log, myid: &usize, player_agents: & mut H
                 ( halite_in_cell >= 100 && halite_in_cell < 200 && num_gen_2 < 0.005) ||
                 ( halite_in_cell >= 50 && halite_in_cell < 100 && num_gen_2 < 0.0005) ||
                 ( halite_in_cell < 50 && num_gen_2 < 0.00005 ) {
-                match map_u.get( p_n.0, p_n.1 ) {
+                match map.ship( cell_pos ) {
                     mapraw::Unit::None => {
                         // log.log(&format!("agent_action_change assign cell: {:?}", agent_action_change));
-                        let y = ( p_n.0 % (map_r.dim).0 + (map_r.dim).0 ) % (map_r.dim).0;
-                        let x = ( p_n.1 % (map_r.dim).1 + (map_r.dim).1 ) % (map_r.dim).1;
+                        let (y,x) = cell_pos.yx();
                         let a_id = agent_action_change.pop().expect("agent_action empty");
                         let mut a = player_agents.get_mut(&a_id).expect("agent id not found");
                         let mut processed = false;
@@ -530,7 +610,9 @@ fn plan_strategy( log: & mut hlt::log::Log, myid: &usize, player_agents: & mut H
                         }
 
                         a.assigned_mine = Some( Coord( (y,x) ) );
-                        a.assigned_dropoff = Some( Coord( (dropoff_pos.0,dropoff_pos.1) ) );
+                        //the mine's cheapest structure to return to, not necessarily the one the search started from
+                        let dropoff = layers.dist_field.cheapest( y, x ).map_or( dropoff_pos.yx(), |d| d.0 );
+                        a.assigned_dropoff = Some( Coord( dropoff ) );
                         processed = true;
                         
                         log.log(&format!("agent after action change: {:?}", a));
@@ -546,19 +628,32 @@ fn plan_strategy( log: & mut hlt::log::Log, myid: &usize, player_agents: & mut H
     }
 }
 
-fn determine_create_new_agent( player_stats: &HashMap< Player, PlayerStats >, my_id: &usize, map_u: &mapping::mapraw::UnitMap, shipyard_pos: &HashMap<usize,Coord>, turn_num: &usize, max_turn: &usize, is_end_game: &bool ) -> bool {
+//cells enemy ships mined within this many turns count as contested
+const ENEMY_MINING_TURNS: usize = 5;
+//every contested cell this close to one of my structures allows one more ship, up to MAX_CONTEST_SHIPS
+const CONTEST_RADIUS: i32 = 8;
+const MAX_CONTEST_SHIPS: usize = 3;
+
+fn determine_create_new_agent( player_stats: &HashMap< Player, PlayerStats >, my_id: &usize, map: &GameMap, history: &HaliteHistory, constants: &hlt::constants::Constants, turn_num: &usize, is_end_game: &bool ) -> bool {
 
-    let my_shipyard_pos = shipyard_pos.get( my_id ).expect("shipyard position not found");
+    let my_shipyard_pos = map.structures( *my_id ).iter().find(|s| s.0 == -1 ).expect("shipyard position not found").1;
     
-    let pos_empty = if let mapraw::Unit::None = map_u.get( (my_shipyard_pos.0).0, (my_shipyard_pos.0).1 ) {
-        true
-    } else {
-        false
-    };
+    let pos_empty = !map.is_occupied( my_shipyard_pos );
+
+    //a new ship has to be able to bring back its cost from what the map will still hold
+    let ships_total : usize = player_stats.values().map(|s| s.ships ).sum();
+    let turns_left = constants.max_turns.saturating_sub( *turn_num );
+    let worth_it = history.projected_remaining( turns_left ) / ( ships_total + 1 ) > constants.ship_cost;
+
+    //enemies mining close to home take halite my ships would otherwise get, more ships contest it
+    let contested = history.enemy_mining( *my_id, *turn_num, ENEMY_MINING_TURNS ).iter()
+        .filter(|c| map.structures( *my_id ).iter().any(|s| torus::wrapped_l1( c.0, s.1.yx(), map.dim ) <= CONTEST_RADIUS ) )
+        .count();
+    let max_ships = 17 + contested.min( MAX_CONTEST_SHIPS );
         
     let create = match player_stats.get( &Player(*my_id) ) {
         Some(stats) => {
-            if (stats.score > 1000 + turn_num * 5 ) && stats.ships < 17 && pos_empty  && !*is_end_game && *turn_num <= (*max_turn * 7 ) / 10 {
+            if (stats.score > 1000 + turn_num * 5 ) && stats.ships < max_ships && pos_empty  && !*is_end_game && *turn_num <= (constants.max_turns * 7 ) / 10 && worth_it {
                 true
             } else {
                 false
@@ -571,186 +666,435 @@ fn determine_create_new_agent( player_stats: &HashMap< Player, PlayerStats >, my
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let rng_seed: u64 = if args.len() > 1 {
-        args[1].parse().unwrap()
-    } else {
-        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    let mut args: Vec<String> = env::args().collect();
+
+    //local games:
+    //  my_bot --simulate <file with an engine init frame> [seed]
+    //  my_bot --simulate-map <map seed> <map size> <num players> [seed]
+    //  my_bot --inspect-replay <replay file>
+    //  my_bot --replay-input <transcript file> [seed]
+    //any mode that plays a game also takes --write-replay <file> to save it for the viewer,
+    //a game against the engine takes --record-input <file> to save everything read from it,
+    //--render <dir> [--render-format png|ppm] draws every turn as an image and ansi text
+    let replay_path = take_flag_value( & mut args, "--write-replay" );
+    let record_path = take_flag_value( & mut args, "--record-input" );
+    let render_format = take_flag_value( & mut args, "--render-format" ).unwrap_or_else(|| "png".to_string() );
+    let frames = take_flag_value( & mut args, "--render" ).map(|dir| {
+        render::FrameWriter::new( &dir, &render_format ).unwrap_or_else(|e| panic!("can't render to {}: {}", dir, e) )
+    });
+
+    if args.len() > 2 && args[1] == "--inspect-replay" {
+        inspect_replay( &args[2], frames );
+        return;
+    }
+
+    let mut simulate_from = None;
+    let mut simulate_map = None;
+    let mut transcript_from = None;
+    if args.len() > 2 && args[1] == "--replay-input" {
+        transcript_from = Some( args.remove(2) );
+        args.remove(1);
+    } else if args.len() > 2 && args[1] == "--simulate" {
+        simulate_from = Some( args.remove(2) );
+        args.remove(1);
+    } else if args.len() > 4 && args[1] == "--simulate-map" {
+        let spec = args.drain(1..5).skip(1).map(|x| x.parse::<u64>().expect("--simulate-map expects numbers") ).collect::<Vec<_>>();
+        simulate_map = Some( (spec[0], spec[1] as i32, spec[2] as usize) );
+    }
+
+    let seed_arg: Option<u64> = args.get(1).map(|x| x.parse().unwrap() );
+
+    if let Some(path) = transcript_from {
+        let reader = BufReader::new( File::open( &path ).unwrap_or_else(|e| panic!("can't open {}: {}", path, e) ) );
+        let log = Rc::new(RefCell::new(hlt::log::Log::new()));
+        let stdout = io::stdout();
+        if let Err(e) = replay_transcript( &log, reader, & mut stdout.lock(), seed_arg, replay_path, frames ) {
+            log.borrow_mut().panic(&format!("Error: {}.", e));
+        }
+        return;
+    }
+
+    let rng_seed: u64 = seed_arg.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() );
+
+    if let Some(path) = simulate_from {
+        let file = File::open( &path ).unwrap_or_else(|e| panic!("can't open {}: {}", path, e) );
+        let init = hlt::protocol::InitFrame::parse( & mut hlt::input::Input::from_reader( BufReader::new( file ) ) )
+            .unwrap_or_else(|e| panic!("can't read init frame from {}: {}", path, e) );
+        let mut shipyards = init.shipyards.clone();
+        shipyards.sort_by_key(|s| s.player_id );
+        let shipyards = shipyards.iter().map(|s| s.pos ).collect::<Vec<_>>();
+        simulate( init.constants, init.halite, &shipyards, rng_seed, 0, replay_path, frames );
+        return;
+    }
+
+    if let Some((map_seed, size, num_players)) = simulate_map {
+        let generated = mapping::mapgen::generate( map_seed, size, size, num_players ).expect("map generation failed");
+        //official game length grows from 400 turns at 32x32 to 500 at 64x64
+        let constants = hlt::constants::Constants {
+            max_turns: 400 + 100 * ( size as usize - 32 ) / 32,
+            ..Default::default()
+        };
+        simulate( constants, generated.halite, &generated.shipyards, rng_seed, map_seed, replay_path, frames );
+        return;
+    }
+
+    let log = Rc::new(RefCell::new(hlt::log::Log::new()));
+    let mut input = hlt::input::Input::new();
+    if let Some(path) = record_path {
+        let file = File::create( &path ).unwrap_or_else(|e| panic!("can't create {}: {}", path, e) );
+        record_transcript( & mut input, file, rng_seed ).unwrap_or_else(|e| panic!("can't write {}: {}", path, e) );
+    }
+    let stdout = io::stdout();
+
+    //nothing sensible can be answered once the input is unreadable, log it and leave
+    if let Err(e) = run( &log, & mut input, & mut stdout.lock(), rng_seed, replay_path, frames ) {
+        log.borrow_mut().panic(&format!("Error: {}.", e));
+    }
+}
+
+//header line of an input transcript, followed by the seed of the recorded game
+const TRANSCRIPT_SEED: &str = "#seed";
+
+//copies everything read from the engine into transcript from now on, after a header with the seed
+fn record_transcript<R: BufRead, W: Write + 'static>( input: & mut hlt::input::Input<R>, mut transcript: W, rng_seed: u64 ) -> io::Result<()> {
+    writeln!( transcript, "{} {}", TRANSCRIPT_SEED, rng_seed )?;
+    input.record_to( transcript );
+    Ok(())
+}
+
+//plays the game of a recorded transcript again with the seed of its header, an explicit seed
+//wins and a transcript without a header plays with seed 0
+fn replay_transcript<R: BufRead, W: Write>( log: &Rc<RefCell<hlt::log::Log>>, mut reader: R, out: & mut W, seed_arg: Option<u64>, replay_path: Option<String>, frames: Option<render::FrameWriter> ) -> hlt::error::Result<()> {
+    let mut first = String::new();
+    reader.read_line( & mut first )?;
+    let recorded_seed = match first.trim().strip_prefix( TRANSCRIPT_SEED ) {
+        Some(x) => {
+            let token = x.trim();
+            let seed = token.parse::<u64>().map_err(|_| hlt::error::Error::Parse { token: token.to_string(), expected: "transcript seed".to_string() } )?;
+            first.clear();
+            Some( seed )
+        },
+        None => None,
     };
-    let seed_bytes: Vec<u8> = (0..16).map(|x| ((rng_seed >> (x % 8)) & 0xFF) as u8).collect();
-    let mut rng: XorShiftRng = SeedableRng::from_seed([
+    let rng_seed = seed_arg.or( recorded_seed ).unwrap_or(0);
+    let mut input = hlt::input::Input::from_reader( io::Cursor::new( first ).chain( reader ) );
+    run( log, & mut input, out, rng_seed, replay_path, frames )
+}
+
+//removes `flag <value>` from the arguments wherever it appears and returns the value
+fn take_flag_value( args: & mut Vec<String>, flag: &str ) -> Option<String> {
+    let i = args.iter().position(|x| x == flag )?;
+    if i + 1 >= args.len() {
+        panic!("{} expects a file name", flag);
+    }
+    let value = args.remove( i + 1 );
+    args.remove( i );
+    Some( value )
+}
+
+//every random choice of the bot comes from this, so a seed and the engine input reproduce a game
+fn seeded_rng( rng_seed: u64 ) -> XorShiftRng {
+    let seed_bytes: Vec<u8> = (0..16).map(|x| ((rng_seed >> (8 * (x % 8))) & 0xFF) as u8).collect();
+    SeedableRng::from_seed([
         seed_bytes[0], seed_bytes[1], seed_bytes[2], seed_bytes[3],
         seed_bytes[4], seed_bytes[5], seed_bytes[6], seed_bytes[7],
         seed_bytes[8], seed_bytes[9], seed_bytes[10], seed_bytes[11],
         seed_bytes[12], seed_bytes[13], seed_bytes[14], seed_bytes[15]
-    ]);
-
-    let mut log = Rc::new(RefCell::new(hlt::log::Log::new()));
-    let mut input = hlt::input::Input::new(&log);
-
-    //inputs:
-    //
-    //constants
-    //number of players
-    //my id
-    //for each player:
-    //  player id, shipyard x, shipyard y
-    //map width, map height
-    //resource map of halite:
-    //  [0][0] [0][1]... (1st row)
-    //  [1][0] [1][1]...
-    //  ...
-        
-    let constants = hlt::constants::Constants::new(log.borrow_mut().deref_mut(), &input.read_and_return_line());
-    input.read_and_parse_line();
-    let num_players : usize = input.next();
-    let my_id : usize = input.next();
-    let mut players = vec![];
-    let mut shipyard_pos = HashMap::new();
-    for _ in 0..num_players {
-        input.read_and_parse_line();
-        let player_id : usize = input.next();
-        let shipyard_x : i32 = input.next();
-        let shipyard_y : i32 = input.next();        
-        players.push( (player_id, shipyard_y, shipyard_x ) );
-        shipyard_pos.insert( player_id, Coord((shipyard_y, shipyard_x)) );
-    }
-    log.borrow_mut().open(my_id);
-    input.read_and_parse_line();    
-    let map_w : i32 = input.next();
-    let map_h : i32 = input.next();
-    let mut rawmaps;
-    {
-        let mut v = vec![ vec![ 0; map_w as usize]; map_h as usize];
-        for i in 0..map_h as usize {
-            input.read_and_parse_line();
-            for j in 0..map_w as usize {
-                let amount : usize = input.next();
-                v[i][j] = amount;
-            }
+    ])
+}
+
+//drives the bot from any engine line source and writes commands to any sink, so the
+//turn loop can be exercised against canned engine frames as well as the real engine.
+//returns once the engine closes the input, or with the error that made the input unreadable
+fn run<R: BufRead, W: Write>( log: &Rc<RefCell<hlt::log::Log>>, input: & mut hlt::input::Input<R>, out: & mut W, rng_seed: u64, replay_path: Option<String>, frames: Option<render::FrameWriter> ) -> hlt::error::Result<()> {
+
+    let init = hlt::protocol::InitFrame::parse( input )?;
+
+    //the bot only sees its own commands, the other players' moves stay empty in its replay
+    let mut replay = replay_path.map(|path| {
+        let mut w = replay::writer::ReplayWriter::new( &init, 0 );
+        w.set_name( init.my_id, "origin" );
+        (path, w)
+    });
+
+    let mut bot = Bot::new( log, init, rng_seed );
+    bot.frames = frames;
+
+    let result = play_turns( log, input, out, & mut bot, replay.as_mut().map(|r| & mut r.1 ) );
+
+    //whatever ended the game, what was played so far still makes a replay
+    if let Some((path, w)) = replay.as_ref() {
+        if let Err(e) = w.write( path ) {
+            log.borrow_mut().log(&format!("Error: can't write replay {}: {}", path, e));
         }
-        let rm = mapping::mapraw::ResourceMap { map: v, dim: (map_h, map_w) };
-        rawmaps = mapping::mapraw::RawMaps {
-            map_r: rm,
-            map_u: Default::default(),
-            map_d: Default::default(),
+    }
+    log.borrow_mut().flush();
+
+    result
+}
+
+//answers turn frames until the engine closes the input
+fn play_turns<R: BufRead, W: Write>( log: &Rc<RefCell<hlt::log::Log>>, input: & mut hlt::input::Input<R>, out: & mut W, bot: & mut Bot, mut replay: Option<& mut replay::writer::ReplayWriter> ) -> hlt::error::Result<()> {
+
+    writeln!( out, "origin" )?;
+    out.flush()?;
+
+    loop {
+
+        let frame = match hlt::protocol::TurnFrame::parse( input, &bot.init ) {
+            Ok(x) => x,
+            Err(hlt::error::Error::UnexpectedEof) => {
+                log.borrow_mut().log("Input connection from server closed. Exiting...");
+                return Ok(());
+            },
+            Err(hlt::error::Error::InvalidFrame(msg)) => {
+                //the frame was read to its end but can't be trusted, answer with a no-op turn
+                log.borrow_mut().log(&format!("Error: turn frame: {}. Skipping turn.", msg));
+                writeln!( out )?;
+                out.flush()?;
+                continue;
+            },
+            Err(e) => {
+                //somewhere inside a frame, whatever is read next would be misread
+                return Err(e);
+            },
         };
+
+        let command_queue = bot.play_turn( &frame );
+
+        if let Some(w) = replay.as_mut() {
+            let commands = Some( (bot.init.my_id, command_queue.clone()) ).into_iter().collect();
+            w.record_turn( &frame, &commands );
+        }
+
+        writeln!( out, "{}", hlt::command::serialize( &command_queue ) )?;
+        out.flush()?;
     }
+}
 
-    log.borrow_mut().log(&format!("shipyards: {:?}", players ));
-    
-    log.borrow_mut().log(&format!("constants: {}", constants));
-    log.borrow_mut().log(&format!("max turns: {}", constants.max_turns));
-    log.borrow_mut().log(&format!("num players: {}", num_players));
-    log.borrow_mut().log(&format!("my id: {}", my_id));
-    log.borrow_mut().log(&format!("map width: {}", map_w));
-    log.borrow_mut().log(&format!("map height: {}", map_h));
+//plays every seat of the game with this bot against the local engine and prints the result
+fn simulate( constants: hlt::constants::Constants, halite: Vec<Vec<usize>>, shipyards: &[(i32,i32)], rng_seed: u64, map_seed: u64, replay_path: Option<String>, frames: Option<render::FrameWriter> ) {
 
-    log.borrow_mut().flush();
-    println!("origin");
+    let mut engine = sim::engine::Engine::new( constants, halite, shipyards );
 
-    log.borrow_mut().log(&format!("Successfully created bot! My Player ID is {}. Bot rng seed is {}.", my_id, rng_seed));
+    let mut bots = (0..shipyards.len()).map(|id| {
+        let log = Rc::new(RefCell::new(hlt::log::Log::new()));
+        let mut bot = Bot::new( &log, engine.init_frame( id ), rng_seed );
+        bot.frames = frames.clone();
+        bot
+    }).collect::<Vec<_>>();
 
-    let mut player_stats : HashMap< Player, PlayerStats > = Default::default();
+    let mut replay = replay_path.as_ref().map(|_| {
+        let mut w = replay::writer::ReplayWriter::new( &engine.init_frame( 0 ), map_seed );
+        for id in 0..shipyards.len() {
+            w.set_name( id, "origin" );
+        }
+        w
+    });
+
+    //ships each player built and lost in collisions, by player id
+    let mut owner_of = HashMap::new();
+    let mut built = vec![ 0; shipyards.len() ];
+    let mut lost = vec![ 0; shipyards.len() ];
+
+    engine.run(
+        |id, frame| bots[id].play_turn( frame ),
+        |frame, commands, events| {
+            if let Some(w) = replay.as_mut() {
+                w.record_turn( frame, &commands.iter().cloned().enumerate().collect() );
+            }
+            for e in events.iter() {
+                match *e {
+                    sim::engine::SimEvent::Spawn { owner, id } => {
+                        owner_of.insert( id, owner );
+                        built[owner] += 1;
+                    },
+                    sim::engine::SimEvent::Construct { owner, id, pos } => {
+                        println!("turn {}: player {} built dropoff {} at (y: {}, x: {})", frame.turn_num, owner, id, pos.0, pos.1 );
+                    },
+                    sim::engine::SimEvent::Collision { ref ships } => {
+                        for owner in ships.iter().filter_map(|id| owner_of.get( id ) ) {
+                            lost[*owner] += 1;
+                        }
+                    },
+                }
+            }
+        }
+    );
 
-    let mut agents : HashMap<Player, HashMap<usize,Agent> > = HashMap::new();
+    if let (Some(path), Some(w)) = (replay_path, replay) {
+        w.write( &path ).unwrap_or_else(|e| panic!("can't write replay {}: {}", path, e) );
+        println!("replay written to {}", path );
+    }
+
+    for (id,score) in engine.scores().iter().enumerate() {
+        println!("player {}: {} halite, {} ships ({} built, {} lost in collisions), {} dropoffs", id, score, engine.ships.values().filter(|s| s.owner == id ).count(), built[id], lost[id], engine.players[id].dropoffs.len() );
+    }
+    println!("rankings: {:?}", engine.rankings() );
+}
 
-    let mut agents_removed : HashMap<Player, Vec<Agent> > = HashMap::new();
+//prints how each player's halite, ships and dropoffs developed over a recorded game
+//and with a frame writer draws every recorded turn
+fn inspect_replay( path: &str, frames: Option<render::FrameWriter> ) {
+
+    let r = replay::reader::read( path ).unwrap_or_else(|e| panic!("can't read replay {}: {}", path, e) );
+
+    println!("map seed: {}, size: {}x{}, turns: {}", r.map_seed, r.initial_halite.first().map_or( 0, |x| x.len() ), r.initial_halite.len(), r.turns.len() );
+    for p in r.players.iter() {
+        println!("player {} ({}): shipyard (y: {}, x: {})", p.id, p.name, p.shipyard.0, p.shipyard.1 );
+    }
+
+    let step = ( r.turns.len() / 10 ).max( 1 );
+    for t in r.turns.iter().filter(|t| t.turn_num % step == 0 || t.turn_num + 1 == r.turns.len() ) {
+        let halite_left = t.map.total_halite();
+        let mut line = format!("turn {:>3}: sea {:>7}", t.turn_num, halite_left );
+        for p in r.players.iter() {
+            let s = t.stats.get( &p.id ).cloned().unwrap_or_default();
+            let commands = t.commands.get( &p.id ).map_or( 0, |c| c.len() );
+            line.push_str( &format!(" | p{} halite {:>6} deposited {:>6} ships {:>3} dropoffs {} commands {:>3}", p.id, s.halite, s.deposited, s.ships, s.dropoffs, commands ) );
+        }
+        println!("{}", line );
+    }
+
+    if let Some(frames) = frames {
+        for t in r.turns.iter() {
+            let scene = render::Scene::new( &t.map, vec![], r.constants.max_halite );
+            frames.write( &format!("turn-{:03}", t.turn_num ), &scene ).unwrap_or_else(|e| panic!("can't render turn {}: {}", t.turn_num, e) );
+        }
+    }
+}
+
+//per game state of the bot, fed one parsed frame per turn by either the engine loop or the simulator
+struct Bot {
+    log: Rc<RefCell<hlt::log::Log>>,
+    init: hlt::protocol::InitFrame,
+    history: HaliteHistory,
+    map: GameMap,
+    player_stats: HashMap< Player, PlayerStats >,
+    agents: HashMap<Player, HashMap<usize,Agent> >,
+    agents_removed: HashMap<Player, Vec<Agent> >,
+    rng: XorShiftRng,
+    halite_sum: HaliteSum,
+    congestion: Congestion,
+    frames: Option<render::FrameWriter>, //draws the bot's view of every turn when set
+}
+
+impl Bot {
+
+    fn new( log: &Rc<RefCell<hlt::log::Log>>, init: hlt::protocol::InitFrame, rng_seed: u64 ) -> Bot {
+
+        let constants = &init.constants;
+        let num_players = init.num_players;
+        let my_id = init.my_id;
+        let (map_h, map_w) = init.dim;
+        let players = init.shipyards.iter().map(|s| (s.player_id, s.pos.0, s.pos.1) ).collect::<Vec<_>>();
+        log.borrow_mut().open(my_id);
+
+        let mut map = GameMap::from_halite( &init.halite );
+
+        let halite_sum = HaliteSum::new( &map );
+
+        //shipyards count as dropoff points with id -1, the frames only report the dropoffs built later
+        for s in init.shipyards.iter() {
+            let p = map.pos( s.pos.0, s.pos.1 );
+            map.set_structure( s.player_id, -1, p );
+        }
+
+        log.borrow_mut().log(&format!("shipyards: {:?}", players ));
     
-    loop {
+        log.borrow_mut().log(&format!("constants: {}", constants));
+        log.borrow_mut().log(&format!("max turns: {}", constants.max_turns));
+        log.borrow_mut().log(&format!("num players: {}", num_players));
+        log.borrow_mut().log(&format!("my id: {}", my_id));
+        log.borrow_mut().log(&format!("map width: {}", map_w));
+        log.borrow_mut().log(&format!("map height: {}", map_h));
+
+        log.borrow_mut().log(&format!("Successfully created bot! My Player ID is {}. Bot rng seed is {}.", my_id, rng_seed));
+        log.borrow_mut().flush();
+
+        Bot {
+            log: log.clone(),
+            init,
+            history: HaliteHistory::new( &map ),
+            map,
+            player_stats: Default::default(),
+            agents: HashMap::new(),
+            agents_removed: HashMap::new(),
+            rng: seeded_rng( rng_seed ),
+            halite_sum,
+            congestion: Congestion::new(),
+            frames: None,
+        }
+    }
+
+    fn play_turn( & mut self, frame: &hlt::protocol::TurnFrame ) -> Vec<Command> {
 
-        //input:
-        //
-        //turn num
-        //for each player:
-        //  player_id  num_ships num_dropoffs halite_amount
-        //  ship_id1 coord_x coord_y halite_value
-        //  ship_id2 coord_x coord_y halite_value
-        //  ..
-        //  (num_ships)
-        //  dropoff_id1 coord_x coord_y
-        //  dropoff_id2 coord_x coord_y
-        //  ..
-        //  (num_dropoffs)
-        //
-        //map_update_count
-        //
         let t_start = Instant::now();
 
-        rawmaps.map_d = mapping::mapraw::DropoffMap::from( (map_h,map_w) );
-        rawmaps.map_u = mapping::mapraw::UnitMap::from( (map_h,map_w) );
-        
-        input.read_and_parse_line();
-        let turn_num : usize = input.next();
+        let log = &self.log;
+        let constants = &self.init.constants;
+        let my_id = self.init.my_id;
+        let game_map = & mut self.map;
+        let player_stats = & mut self.player_stats;
+        let agents = & mut self.agents;
+        let agents_removed = & mut self.agents_removed;
+        let rng = & mut self.rng;
+        let congestion = & mut self.congestion;
+
+        let turn_num = frame.turn_num;
 
         log.borrow_mut().log(&format!("turn {} -------------------------------------", turn_num ));
         
-        for _ in 0..num_players {
+        for p in frame.players.iter() {
 
-            input.read_and_parse_line();
-            let player_id : usize = input.next();
-            let num_ships : usize = input.next();
-            let num_dropoffs : usize = input.next();
-            let player_halite : usize = input.next();
+            let player_id = p.player_id;
             
-            player_stats.insert( Player(player_id), PlayerStats{ score: player_halite, ships: num_ships, dropoffs: num_dropoffs } );
+            player_stats.insert( Player(player_id), PlayerStats{ score: p.halite, ships: p.ships.len(), dropoffs: p.dropoffs.len() } );
             
-            for _ in 0..num_ships {
-                input.read_and_parse_line();
-                let ship_id : usize = input.next();
-                let x : i32 = input.next();
-                let y : i32 = input.next();
-                let ship_halite : usize = input.next();
-
-                if player_id == my_id {
-                    log.borrow_mut().log(&format!("ship id: {}, y: {}, x: {}", ship_id, y, x));
+            if player_id == my_id {
+                for s in p.ships.iter() {
+                    log.borrow_mut().log(&format!("ship id: {}, y: {}, x: {}", s.id, s.pos.0, s.pos.1));
                 }
-                
-                rawmaps.map_u.set( y, x, mapping::mapraw::Unit::Ship{ player: player_id, id: ship_id, halite: ship_halite } );
-            }
-
-            for _ in 0..num_dropoffs {
-                input.read_and_parse_line();
-                let dropoff_id : usize = input.next();
-                let x : i32 = input.next();
-                let y : i32 = input.next();
-                rawmaps.map_d.set( dropoff_id as i32, y, x, mapping::mapraw::Player( player_id ) );
             }
+        }
 
-            //also count shipyard as a dropoff point
-            for i in players.iter() {
-                let id = i.0;
-                let y = i.1;
-                let x = i.2;
-                rawmaps.map_d.set( -1i32, y, x, mapping::mapraw::Player( id ) );
-            }
-            
+        let map_events = game_map.apply_frame( frame );
+        let game_map = &*game_map;
+        for e in map_events.iter() {
+            log.borrow_mut().log(&format!("map event: {}", e));
         }
             
-        input.read_and_parse_line();
-        let map_update_count : usize = input.next();
-        log.borrow_mut().log(&format!("resource update count: {}", map_update_count));        
-        for _ in 0..map_update_count {
-            input.read_and_parse_line();
-            let x : usize = input.next();
-            let y : usize = input.next();
-            let halite_amount : usize = input.next();
-            rawmaps.map_r.map[y][x] = halite_amount;
-            log.borrow_mut().log(&format!("resource update [{}][{}]: {}", y,x,halite_amount));
-        }
-
-        log.borrow_mut().log(&format!("unit map: {:?}", rawmaps.map_u.invmap ));
+        log.borrow_mut().log(&format!("resource update count: {}", frame.map_updates.len()));        
+        for u in frame.map_updates.iter() {
+            let (y,x) = u.pos;
+            log.borrow_mut().log(&format!("resource update [{}][{}]: {}", y,x,u.halite));
+        }
+        self.halite_sum.update( &frame.map_updates );
+
+        log.borrow_mut().log(&format!("ships: {:?}", game_map.all_ships() ));
+
+        self.history.record_turn( turn_num, &frame.map_updates, game_map );
+        log.borrow_mut().log(&format!("halite left: {}, projected at game end: {}", self.history.total(), self.history.projected_remaining( constants.max_turns.saturating_sub( turn_num ) ) ));
+        for k in player_stats.keys() {
+            log.borrow_mut().log(&format!("player {}: halite mined: {}", k.0, self.history.mined_by( k.0 ) ));
+        }
+        let enemy_mining = self.history.enemy_mining( my_id, turn_num, ENEMY_MINING_TURNS );
+        log.borrow_mut().log(&format!("cells mined by enemies recently: {:?}", enemy_mining ));
+        for &((y,x),player) in enemy_mining.iter() {
+            log.borrow_mut().log(&format!("halite of [{}][{}] mined by player {}: {:?}", y, x, player, self.history.cell_history( y, x ) ));
+        }
+        for s in self.init.shipyards.iter().filter(|s| s.player_id == my_id ) {
+            log.borrow_mut().log(&format!("depletion rate around shipyard: {:.1}", self.history.depletion_rate( s.pos.0, s.pos.1 ) ));
+        }
         
         //synchronize agent information
         for k in player_stats.keys() {
             
-            let player_agents = rawmaps.map_u.get_player_agents( &k.0 );
             if !agents.contains_key( k ) {
                 agents.insert( k.clone(), HashMap::new() );
             }
-            let (updated_agents, removed) = synchronize_player_agents( agents.get( k ).unwrap(), player_agents );
-            log.borrow_mut().log(&format!("player {}: agents updated count: {}", k.0, updated_agents.len() ));
-            *agents.get_mut( k ).unwrap() = updated_agents;
+            let removed = synchronize_player_agents( & mut log.borrow_mut(), agents.get_mut( k ).unwrap(), k.0, &map_events );
+            log.borrow_mut().log(&format!("player {}: agents updated count: {}", k.0, agents[k].len() ));
                                  
             if !agents_removed.contains_key( k ) {
                agents_removed.insert( k.clone(), Default::default() );
@@ -760,24 +1104,35 @@ fn main() {
         }
 
         //update macro strategy, assign task to each worker
+        let player_ids = self.init.shipyards.iter().map(|s| s.player_id ).collect::<Vec<_>>();
+        let layers = TurnLayers {
+            halite_sum: &self.halite_sum,
+            inspiration: InspirationMap::new( game_map, constants, &player_ids ),
+            dist_field: DistanceField::new( game_map, my_id, constants.move_cost_ratio ),
+            path_costs: PathCosts::new( game_map, my_id, constants.move_cost_ratio ),
+            threat: ThreatMap::new( game_map, my_id, constants ),
+            map: game_map,
+        };
+
         let mut is_end_game = false;
-        if constants.max_turns - turn_num <= (rawmaps.map_r.dim.0 * 6 / 10) as usize {
+        if constants.max_turns - turn_num <= (game_map.dim.0 * 6 / 10) as usize {
             is_end_game = true;
             for a in agents.get_mut(&Player(my_id)).expect("player agent") {
-                let mut shortest_dist = 99999999;
-                let mut dest = None;
-                for (dropoff_id,dropoff_pos) in rawmaps.map_d.invmap.get(&my_id).expect("player id not found for dropoff map").iter() {
-                    let dist = (((a.1.pos.0).0)-dropoff_pos.0).abs() + (((a.1.pos.0).1)-dropoff_pos.1).abs();
-                    if shortest_dist > dist {
-                        shortest_dist = dist;
-                        dest = Some(*dropoff_pos);
-                    }
-                }
-                a.1.assigned_dropoff = Some(Coord(dest.unwrap()));
+                let (y,x) = a.1.pos.0;
+                let dest = layers.dist_field.nearest( y, x ).expect("no dropoff to return to");
+                log.borrow_mut().log(&format!("end game: ship {} is {} turns from home, {} halite burned on the cheapest way", a.1.id, layers.dist_field.turns( y, x ), layers.dist_field.burn( y, x )));
+                a.1.assigned_dropoff = Some(Coord(dest));
                 a.1.status = AgentStatus::EndGame;
             }
         } else {
-            plan_strategy( & mut log.borrow_mut(), &my_id, agents.get_mut(&Player(my_id)).expect("player agent"), &rawmaps.map_r, &rawmaps.map_d, &rawmaps.map_u );
+            plan_strategy( & mut log.borrow_mut(), &my_id, agents.get_mut(&Player(my_id)).expect("player agent"), &layers, rng );
+
+            //returning ships head for whichever structure is cheapest to reach from where they are now
+            for a in agents.get_mut(&Player(my_id)).expect("player agent").values_mut().filter(|a| a.is_moving_to_dropoff() ) {
+                if let Some((dest,_)) = layers.dist_field.cheapest( (a.pos.0).0, (a.pos.0).1 ) {
+                    a.set_task_dropoff( Coord( dest ) );
+                }
+            }
         }
 
         log.borrow_mut().log(&format!("agents: {:?}", agents.get_mut(&Player(my_id)).expect("player agent") ) );
@@ -786,42 +1141,222 @@ fn main() {
         let mut queued_movements = vec![];
         let mut my_agents = agents.get_mut( &Player(my_id) );
         for a in my_agents.iter_mut() {
-            a.iter_mut().for_each(|( agent_id, agent )| {
-                queued_movements.push( agent.execute( &rawmaps.map_r, & mut log.borrow_mut() ) );
+            //in id order, hash map order would make the seeded rng draws differ between runs
+            let mut ordered = a.iter_mut().collect::<Vec<_>>();
+            ordered.sort_by_key(|x| *x.0 );
+            ordered.into_iter().for_each(|( agent_id, agent )| {
+                queued_movements.push( agent.execute( game_map, & mut log.borrow_mut(), rng ) );
             });
         }
 
         log.borrow_mut().log(&format!("queued movement: {:?}", queued_movements ) );
             
         //todo: schedule agent movement
-        let movements = schedule( queued_movements, &rawmaps.map_r, &rawmaps.map_d, & mut rawmaps.map_u, &is_end_game, &my_id );
+        //schedule claims destination cells on its own copy, the tracked map stays as the frame left it
+        let mut scheduled = game_map.clone();
+        let movements = schedule( queued_movements, & mut scheduled, &is_end_game, &my_id, &layers, congestion, rng );
 
         log.borrow_mut().log(&format!("inspecting scheduled movements:") );
         movements.iter().inspect(|x| log.borrow_mut().log(&format!("{:?}",x)) );
             
         //create new worker if necessary
-        let create_new_agent = determine_create_new_agent( &player_stats, &my_id, &rawmaps.map_u, &shipyard_pos, &turn_num, &constants.max_turns, &is_end_game );
+        //cells claimed by the scheduled moves count as occupied
+        let create_new_agent = determine_create_new_agent( &player_stats, &my_id, &scheduled, &self.history, constants, &turn_num, &is_end_game );
         
         //emit commands
-        let mut command_queue: Vec<String> = vec![];
+        let mut command_queue: Vec<Command> = vec![];
         
         for (id,dir) in movements {
             add_movement_cmd( &id, &dir, & mut command_queue ).expect("add movement failed");
         }
         if create_new_agent {
-            command_queue.push( format!("g") );
+            command_queue.push( Command::Spawn );
         }
 
-        for i in command_queue.drain(..) {
+        let command_queue = hlt::validate::validate( & mut log.borrow_mut(), command_queue, &self.init, frame, game_map );
+
+        for i in command_queue.iter() {
             log.borrow_mut().log(&format!("turn {}, command: {}", turn_num, i));
-            print!("{} ", i);
         }
-        println!();
         
+        //draw the turn as the bot saw it, with the mine cells its ships are headed for
+        if let Some(frames) = self.frames.as_ref() {
+            let mut targets = agents[&Player(my_id)].values().filter_map(|a| a.assigned_mine.map(|c| c.0 ) ).collect::<Vec<_>>();
+            targets.sort();
+            let scene = render::Scene::new( game_map, targets, constants.max_halite );
+            if let Err(e) = frames.write( &format!("p{}-turn-{:03}", my_id, turn_num ), &scene ) {
+                log.borrow_mut().log(&format!("Error: can't render turn {}: {}", turn_num, e));
+            }
+        }
+
         //log time
         let mut t_elapsed = t_start.elapsed();
         let t_elapsed_nanos = t_elapsed.subsec_nanos() as u64;
         let t_elapsed_ms = t_elapsed.as_secs() * 1000 + t_elapsed_nanos / 1000000;
         log.borrow_mut().log(&format!("turn {} elapsed time: {}", turn_num, t_elapsed_ms));
+
+        command_queue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hlt::protocol::tests::{init_text, turn_text};
+
+    //plays a canned game as player 0, returns how run ended and everything written to the engine
+    fn play( text: &str ) -> (hlt::error::Result<()>, String) {
+        let log = Rc::new(RefCell::new(hlt::log::Log::new()));
+        let mut input = hlt::input::Input::from_reader( io::Cursor::new( text.as_bytes().to_vec() ) );
+        let mut out : Vec<u8> = vec![];
+        let result = run( &log, & mut input, & mut out, 7, None, None );
+        (result, String::from_utf8( out ).unwrap())
+    }
+
+    #[test]
+    fn answers_every_frame_with_one_line() {
+        let text = init_text( 0 ) + &(1..5).map( turn_text ).collect::<String>();
+        let (result, out) = play( &text );
+        assert!( result.is_ok() );
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!( lines.len(), 5 );
+        assert_eq!( lines[0], "origin" );
+        //only player 0's ship is ever commanded
+        for l in lines[1..].iter() {
+            let tokens = l.split_whitespace().collect::<Vec<_>>();
+            assert!( tokens.is_empty() || tokens == ["g"] || ( tokens[0] == "m" && tokens[1] == "0" ), "unexpected line '{}'", l );
+        }
+    }
+
+    #[test]
+    fn same_seed_same_answers() {
+        let text = init_text( 0 ) + &(1..5).map( turn_text ).collect::<String>();
+        assert_eq!( play( &text ).1, play( &text ).1 );
+    }
+
+    #[test]
+    fn events_of_ships_without_an_agent_are_skipped() {
+        use mapraw::MapEvent::*;
+        let mut log = hlt::log::Log::new();
+        let mut agents = HashMap::new();
+        let events = [
+            ShipMoved { player: 0, id: 3, from: (1,1), to: (1,2) },
+            CargoChanged { player: 0, id: 3, from: 0, to: 40 },
+            ShipSpawned { player: 0, id: 4, pos: (2,2), halite: 0 },
+            ShipMoved { player: 0, id: 4, from: (2,2), to: (2,3) },
+            CargoChanged { player: 0, id: 4, from: 0, to: 25 },
+            ShipSpawned { player: 1, id: 5, pos: (6,5), halite: 0 },
+        ];
+        let removed = synchronize_player_agents( & mut log, & mut agents, 0, &events );
+        assert!( removed.is_empty() );
+        assert_eq!( agents.keys().collect::<Vec<_>>(), vec![ &4 ] );
+        assert_eq!( ( agents[&4].pos.0, agents[&4].halite ), ( (2,3), 25 ) );
+    }
+
+    //cloneable sink standing in for the transcript file
+    #[derive(Clone,Default)]
+    struct Shared( Rc<RefCell<Vec<u8>>> );
+
+    impl Write for Shared {
+        fn write( & mut self, buf: &[u8] ) -> io::Result<usize> {
+            self.0.borrow_mut().write( buf )
+        }
+        fn flush( & mut self ) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn replay( transcript: &str, seed_arg: Option<u64> ) -> (hlt::error::Result<()>, String) {
+        let log = Rc::new(RefCell::new(hlt::log::Log::new()));
+        let mut out : Vec<u8> = vec![];
+        let result = replay_transcript( &log, io::Cursor::new( transcript.as_bytes().to_vec() ), & mut out, seed_arg, None, None );
+        (result, String::from_utf8( out ).unwrap())
+    }
+
+    #[test]
+    fn replaying_a_recorded_transcript_gives_the_same_answers() {
+        let text = init_text( 0 ) + &(1..5).map( turn_text ).collect::<String>();
+        let log = Rc::new(RefCell::new(hlt::log::Log::new()));
+        let mut input = hlt::input::Input::from_reader( io::Cursor::new( text.as_bytes().to_vec() ) );
+        let transcript = Shared::default();
+        record_transcript( & mut input, transcript.clone(), 7 ).unwrap();
+        let mut out : Vec<u8> = vec![];
+        assert!( run( &log, & mut input, & mut out, 7, None, None ).is_ok() );
+
+        let recorded = String::from_utf8( transcript.0.borrow().clone() ).unwrap();
+        assert_eq!( recorded, format!("#seed 7\n{}", text ) );
+
+        let (result, replayed) = replay( &recorded, None );
+        assert!( result.is_ok() );
+        assert_eq!( replayed, String::from_utf8( out ).unwrap() );
+        assert_eq!( replay( &recorded, Some( 7 ) ).1, replayed );
+    }
+
+    #[test]
+    fn transcript_without_a_header_plays_with_seed_zero() {
+        let text = init_text( 0 ) + &(1..5).map( turn_text ).collect::<String>();
+        let log = Rc::new(RefCell::new(hlt::log::Log::new()));
+        let mut input = hlt::input::Input::from_reader( io::Cursor::new( text.as_bytes().to_vec() ) );
+        let mut out : Vec<u8> = vec![];
+        assert!( run( &log, & mut input, & mut out, 0, None, None ).is_ok() );
+        assert_eq!( replay( &text, None ).1, String::from_utf8( out ).unwrap() );
+    }
+
+    #[test]
+    fn bad_transcript_seed_is_an_error() {
+        let text = format!("#seed x7\n{}", init_text( 0 ) + &turn_text( 1 ) );
+        match replay( &text, None ) {
+            (Err(hlt::error::Error::Parse { token, .. }), out) => {
+                assert_eq!( token, "x7" );
+                assert!( out.is_empty() );
+            },
+            (x, _) => panic!("expected a parse error, got {:?}", x ),
+        }
+    }
+
+    #[test]
+    fn every_byte_of_the_seed_counts() {
+        for &(a,b) in [ (1u64,2u64), (1 << 40, 2 << 40), (1 << 63, 1 << 62) ].iter() {
+            assert_ne!( seeded_rng( a ).gen::<u64>(), seeded_rng( b ).gen::<u64>(), "seeds {:x} and {:x}", a, b );
+        }
+    }
+
+    #[test]
+    fn invalid_frame_gets_a_no_op_and_the_next_frame_an_answer() {
+        let bad = "1\n0 1 0 4000\n0 8 3 25\n1 0 0 5000\n1\n4 9 12\n";
+        let (result, out) = play( &( init_text( 0 ) + bad + &turn_text( 2 ) ) );
+        let (_, expected) = play( &( init_text( 0 ) + &turn_text( 2 ) ) );
+        assert!( result.is_ok() );
+        assert_eq!( out, expected.replacen( "origin\n", "origin\n\n", 1 ) );
+    }
+
+    #[test]
+    fn unreadable_frame_ends_the_game_with_an_error() {
+        let (result, out) = play( &( init_text( 0 ) + "1\n0 1 0 4000\n0 one 3 25\n" + &turn_text( 2 ) ) );
+        match result {
+            Err(hlt::error::Error::Parse { .. }) => {},
+            x => panic!("expected a parse error, got {:?}", x ),
+        }
+        assert_eq!( out, "origin\n" );
+    }
+
+    #[test]
+    fn replay_is_written_when_the_game_ends_with_an_error() {
+        let path = std::env::temp_dir().join( format!("origin-run-{}.hlt", std::process::id() ) );
+        let log = Rc::new(RefCell::new(hlt::log::Log::new()));
+        let text = init_text( 0 ) + &turn_text( 1 ) + "2\n0 1 0 4000\n0 one 3 25\n";
+        let mut input = hlt::input::Input::from_reader( io::Cursor::new( text.into_bytes() ) );
+        let result = run( &log, & mut input, & mut io::sink(), 7, Some( path.to_string_lossy().to_string() ), None );
+        assert!( result.is_err() );
+        let replay = replay::reader::read( &path ).expect("replay not written");
+        std::fs::remove_file( &path ).unwrap();
+        assert_eq!( replay.turns.len(), 1 );
+    }
+
+    #[test]
+    fn unreadable_init_frame_is_an_error() {
+        let (result, out) = play("not constants\n");
+        assert!( result.is_err() );
+        assert!( out.is_empty() );
     }
 }
@@ -0,0 +1,174 @@
+use mapping::gamemap::GameMap;
+use metric::torus;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+//distance fields towards a player's shipyard and dropoffs, rebuilt every turn:
+//turns is the plain step count to the closest structure, burn is the least halite spent on
+//move costs to reach any structure, each step paying halite/move_cost_ratio of the cell it leaves
+
+pub struct DistanceField {
+    pub dim: (i32,i32), //num rows, num columns
+    turns: Vec<i32>,
+    nearest: Vec<Option<(i32,i32)>>, //structure the fewest moves lead to
+    burn: Vec<usize>,
+    burn_turns: Vec<i32>, //turns taken along the cheapest route
+    target: Vec<Option<(i32,i32)>>, //structure the cheapest route ends at
+}
+
+impl DistanceField {
+
+    pub fn new( map: &GameMap, player: usize, move_cost_ratio: usize ) -> DistanceField {
+
+        let dim = map.dim;
+        let len = ( dim.0 * dim.1 ) as usize;
+        let mut f = DistanceField {
+            dim,
+            turns: vec![ i32::MAX; len ],
+            nearest: vec![ None; len ],
+            burn: vec![ usize::MAX; len ],
+            burn_turns: vec![ i32::MAX; len ],
+            target: vec![ None; len ],
+        };
+
+        let sources = map.structures( player ).iter().map(|x| x.1.yx() ).collect::<Vec<_>>();
+
+        let mut queue = VecDeque::new();
+        let mut heap = BinaryHeap::new();
+        for &s in sources.iter() {
+            let i = f.index( s );
+            f.turns[i] = 0;
+            f.nearest[i] = Some( s );
+            f.burn[i] = 0;
+            f.burn_turns[i] = 0;
+            f.target[i] = Some( s );
+            queue.push_back( s );
+            heap.push( Reverse( (0usize, 0i32, s) ) );
+        }
+
+        while let Some(p) = queue.pop_front() {
+            let ip = f.index( p );
+            let t = f.turns[ip];
+            for d in torus::DIRS.iter() {
+                let n = map.pos( p.0 + d.0, p.1 + d.1 ).yx();
+                let i = f.index( n );
+                if f.turns[i] == i32::MAX {
+                    f.turns[i] = t + 1;
+                    f.nearest[i] = f.nearest[ip];
+                    queue.push_back( n );
+                }
+            }
+        }
+
+        //searching outwards from the structures, the step into p is paid by the neighbour it leaves
+        while let Some(Reverse((burn, turns, p))) = heap.pop() {
+            let ip = f.index( p );
+            if ( burn, turns ) > ( f.burn[ip], f.burn_turns[ip] ) {
+                continue;
+            }
+            for d in torus::DIRS.iter() {
+                let n = map.pos( p.0 + d.0, p.1 + d.1 );
+                let i = f.index( n.yx() );
+                let cost = burn + map.halite( n ) / move_cost_ratio;
+                if ( cost, turns + 1 ) < ( f.burn[i], f.burn_turns[i] ) {
+                    f.burn[i] = cost;
+                    f.burn_turns[i] = turns + 1;
+                    f.target[i] = f.target[ip];
+                    heap.push( Reverse( (cost, turns + 1, n.yx()) ) );
+                }
+            }
+        }
+
+        f
+    }
+
+    //fewest moves to any structure, i32::MAX when the player has none
+    pub fn turns( & self, y: i32, x: i32 ) -> i32 {
+        self.turns[ self.index( (y,x) ) ]
+    }
+
+    pub fn nearest( & self, y: i32, x: i32 ) -> Option<(i32,i32)> {
+        self.nearest[ self.index( (y,x) ) ]
+    }
+
+    //least halite burned on the way to any structure
+    pub fn burn( & self, y: i32, x: i32 ) -> usize {
+        self.burn[ self.index( (y,x) ) ]
+    }
+
+    //structure reached by the cheapest route and the turns that route takes
+    pub fn cheapest( & self, y: i32, x: i32 ) -> Option<((i32,i32),i32)> {
+        let i = self.index( (y,x) );
+        self.target[i].map(|t| (t, self.burn_turns[i]) )
+    }
+
+    fn index( & self, p: (i32,i32) ) -> usize {
+        let (y,x) = torus::wrap( p, self.dim );
+        ( y * self.dim.1 + x ) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    //3x12 map, player 0's shipyard at (1,2) and dropoff at (1,9). columns 1, 3 and 4 hold 100
+    //halite, 10 to leave a cell there, and (1,5) holds 50, everything else is free to cross
+    fn field() -> DistanceField {
+        let halite = (0..3).map(|y| (0..12).map(|x| match (y,x) {
+            (_,1) | (_,3) | (_,4) => 100,
+            (1,5) => 50,
+            _ => 0,
+        }).collect() ).collect::<Vec<Vec<usize>>>();
+        let mut map = GameMap::from_halite( &halite );
+        let (shipyard, dropoff) = ( map.pos( 1, 2 ), map.pos( 1, 9 ) );
+        map.set_structure( 0, -1, shipyard );
+        map.set_structure( 0, 0, dropoff );
+        DistanceField::new( &map, 0, 10 )
+    }
+
+    #[test]
+    fn turns_count_plain_steps_to_the_nearest_structure() {
+        let f = field();
+        assert_eq!( ( f.turns( 1, 2 ), f.nearest( 1, 2 ) ), ( 0, Some( (1,2) ) ) );
+        assert_eq!( ( f.turns( 1, 5 ), f.nearest( 1, 5 ) ), ( 3, Some( (1,2) ) ) );
+        assert_eq!( ( f.turns( 0, 7 ), f.nearest( 0, 7 ) ), ( 3, Some( (1,9) ) ) );
+        //across the seam
+        assert_eq!( ( f.turns( 1, 11 ), f.nearest( 1, 11 ) ), ( 2, Some( (1,9) ) ) );
+        assert_eq!( ( f.turns( 2, 0 ), f.nearest( 2, 0 ) ), ( 3, Some( (1,2) ) ) );
+        assert_eq!( f.turns( -2, -1 ), f.turns( 1, 11 ) );
+    }
+
+    #[test]
+    fn cheapest_route_may_end_at_a_farther_structure() {
+        let f = field();
+        //3 steps to the shipyard burn 5 + 10 + 10, 4 steps to the dropoff only the 5 of the start
+        assert_eq!( f.burn( 1, 5 ), 5 );
+        assert_eq!( f.cheapest( 1, 5 ), Some( ((1,9),4) ) );
+        //the origin pays for leaving its own cell
+        assert_eq!( f.burn( 1, 4 ), 15 );
+        assert_eq!( f.cheapest( 1, 4 ), Some( ((1,9),5) ) );
+        assert_eq!( f.burn( 1, 3 ), 10 );
+        assert_eq!( f.cheapest( 1, 3 ), Some( ((1,2),1) ) );
+        assert_eq!( ( f.burn( 1, 9 ), f.cheapest( 1, 9 ) ), ( 0, Some( ((1,9),0) ) ) );
+    }
+
+    #[test]
+    fn cheapest_route_across_the_seam() {
+        let f = field();
+        //the shipyard is 2 steps away over an expensive column, the dropoff 3 free steps back over the seam
+        assert_eq!( f.nearest( 1, 0 ), Some( (1,2) ) );
+        assert_eq!( f.burn( 1, 0 ), 0 );
+        assert_eq!( f.cheapest( 1, 0 ), Some( ((1,9),3) ) );
+    }
+
+    #[test]
+    fn player_without_structures_has_no_field() {
+        let f = DistanceField::new( &GameMap::new( (4,4) ), 1, 10 );
+        assert_eq!( f.turns( 2, 2 ), i32::MAX );
+        assert_eq!( f.nearest( 2, 2 ), None );
+        assert_eq!( f.burn( 2, 2 ), usize::MAX );
+        assert_eq!( f.cheapest( 2, 2 ), None );
+    }
+}
@@ -0,0 +1,375 @@
+use hlt::protocol::TurnFrame;
+use mapping::mapraw::{MapEvent, Player, Unit};
+use metric::torus;
+use std::collections::{HashMap, HashSet};
+
+//the one map of halite, ships and structures in a single flat row major grid, kept up to date
+//frame by frame; every lookup goes through Position so wrapping is handled in one place
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq,Hash,Ord,PartialOrd)]
+pub struct Position {
+    y: i32,
+    x: i32,
+}
+
+impl Position {
+    //normalizes any (y,x) onto the torus of the given (num rows, num columns), the only way to get
+    //a position so it always lies on the map
+    pub fn new( y: i32, x: i32, dim: (i32,i32) ) -> Position {
+        let (y,x) = torus::wrap( (y,x), dim );
+        Position { y, x }
+    }
+
+    pub fn y( self ) -> i32 {
+        self.y
+    }
+
+    pub fn x( self ) -> i32 {
+        self.x
+    }
+
+    pub fn yx( self ) -> (i32,i32) {
+        ( self.y, self.x )
+    }
+}
+
+#[derive(Clone,Copy,Default)]
+pub struct Cell {
+    pub halite: usize,
+    pub ship: Unit,
+    pub structure: Option<Player>, //owner of a shipyard or dropoff on the cell
+}
+
+#[derive(Clone)]
+pub struct GameMap {
+    pub dim: (i32,i32), //num rows, num columns
+    cells: Vec<Cell>,
+    ships: HashMap<usize,HashMap<usize,Position>>, //player id -> ship id -> position
+    structures: HashMap<usize,Vec<(i32,Position)>>, //player id -> (dropoff id, position), shipyard is -1
+}
+
+impl GameMap {
+
+    pub fn new( dim: (i32,i32) ) -> GameMap {
+        GameMap {
+            dim,
+            cells: vec![ Cell::default(); ( dim.0 * dim.1 ) as usize ],
+            ships: HashMap::new(),
+            structures: HashMap::new(),
+        }
+    }
+
+    //map holding the halite of the init frame, rows of columns, and nothing else yet
+    pub fn from_halite( halite: &[Vec<usize>] ) -> GameMap {
+        let mut m = GameMap::new( ( halite.len() as i32, halite.first().map_or( 0, |r| r.len() ) as i32 ) );
+        for (y,row) in halite.iter().enumerate() {
+            for (x,v) in row.iter().enumerate() {
+                let p = m.pos( y as i32, x as i32 );
+                m.set_halite( p, *v );
+            }
+        }
+        m
+    }
+
+    pub fn pos( & self, y: i32, x: i32 ) -> Position {
+        Position::new( y, x, self.dim )
+    }
+
+    pub fn cell( & self, p: Position ) -> &Cell {
+        &self.cells[ self.index( p ) ]
+    }
+
+    pub fn halite( & self, p: Position ) -> usize {
+        self.cell( p ).halite
+    }
+
+    pub fn set_halite( & mut self, p: Position, halite: usize ) {
+        let i = self.index( p );
+        self.cells[i].halite = halite;
+    }
+
+    pub fn total_halite( & self ) -> usize {
+        self.cells.iter().map(|c| c.halite ).sum()
+    }
+
+    pub fn ship( & self, p: Position ) -> Unit {
+        self.cell( p ).ship
+    }
+
+    pub fn is_occupied( & self, p: Position ) -> bool {
+        !matches!( self.ship( p ), Unit::None )
+    }
+
+    //puts a ship on the cell, whatever was recorded there before is overwritten
+    pub fn set_ship( & mut self, p: Position, unit: Unit ) {
+        let i = self.index( p );
+        self.cells[i].ship = unit;
+        if let Unit::Ship { player, id, .. } = unit {
+            self.ships.entry( player ).or_default().insert( id, p );
+        }
+    }
+
+    pub fn remove_ship( & mut self, p: Position ) {
+        let i = self.index( p );
+        if let Unit::Ship { player, id, .. } = self.cells[i].ship {
+            if let Some(ships) = self.ships.get_mut( &player ) {
+                ships.remove( &id );
+            }
+        }
+        self.cells[i].ship = Unit::None;
+    }
+
+    //position and cargo of a ship as last recorded
+    pub fn find_ship( & self, player: usize, id: usize ) -> Option<(Position,usize)> {
+        let p = *self.ships.get( &player )?.get( &id )?;
+        match self.ship( p ) {
+            Unit::Ship { halite, .. } => Some( (p, halite) ),
+            Unit::None => None,
+        }
+    }
+
+    //(ship id, position) of every ship of a player in id order
+    pub fn ships_of( & self, player: usize ) -> Vec<(usize,Position)> {
+        let mut ret = self.ships.get( &player ).map(|x| x.iter().map(|(id,p)| (*id, *p) ).collect::<Vec<_>>() ).unwrap_or_default();
+        ret.sort();
+        ret
+    }
+
+    //(player id, ship id, position) of every ship on the map in player then ship id order
+    pub fn all_ships( & self ) -> Vec<(usize,usize,Position)> {
+        let mut ret = self.ships.iter().flat_map(|(player,ships)| ships.iter().map( move |(id,p)| (*player, *id, *p) ) ).collect::<Vec<_>>();
+        ret.sort();
+        ret
+    }
+
+    pub fn structure( & self, p: Position ) -> Option<Player> {
+        self.cell( p ).structure
+    }
+
+    pub fn set_structure( & mut self, player: usize, id: i32, p: Position ) {
+        let i = self.index( p );
+        self.cells[i].structure = Some( Player( player ) );
+        let list = self.structures.entry( player ).or_default();
+        list.retain(|x| x.0 != id );
+        list.push( (id, p) );
+        list.sort();
+    }
+
+    //shipyard and dropoffs of a player in dropoff id order, the shipyard comes first with id -1
+    pub fn structures( & self, player: usize ) -> &[(i32,Position)] {
+        self.structures.get( &player ).map_or( &[], |x| &x[..] )
+    }
+
+    //brings halite, ships and dropoffs up to the frame and reports what changed about the ships and dropoffs
+    pub fn apply_frame( & mut self, frame: &TurnFrame ) -> Vec<MapEvent> {
+
+        let mut events = vec![];
+        let mut seen = HashSet::new();
+        let mut placed = vec![];
+
+        //clear every ship that moved or vanished first, so a ship moving into a cell
+        //vacated this turn is not wiped out when the previous occupant gets cleared
+        for p in frame.players.iter() {
+            for s in p.ships.iter() {
+                seen.insert( (p.player_id, s.id) );
+                match self.find_ship( p.player_id, s.id ) {
+                    Some((pos, halite)) => {
+                        if pos.yx() != s.pos {
+                            self.remove_ship( pos );
+                            events.push( MapEvent::ShipMoved { player: p.player_id, id: s.id, from: pos.yx(), to: s.pos } );
+                        }
+                        if halite != s.halite {
+                            events.push( MapEvent::CargoChanged { player: p.player_id, id: s.id, from: halite, to: s.halite } );
+                        }
+                    },
+                    None => {
+                        events.push( MapEvent::ShipSpawned { player: p.player_id, id: s.id, pos: s.pos, halite: s.halite } );
+                    },
+                }
+                placed.push( (p.player_id, s) );
+            }
+        }
+
+        let gone = self.all_ships().into_iter().filter(|x| !seen.contains( &(x.0, x.1) ) ).collect::<Vec<_>>();
+        for (player, id, pos) in gone {
+            let halite = self.find_ship( player, id ).map_or( 0, |x| x.1 );
+            self.remove_ship( pos );
+            events.push( MapEvent::ShipDestroyed { player, id, pos: pos.yx(), halite } );
+        }
+
+        for (player, s) in placed {
+            let p = self.pos( s.pos.0, s.pos.1 );
+            self.set_ship( p, Unit::Ship { player, id: s.id, halite: s.halite } );
+        }
+
+        for p in frame.players.iter() {
+            for d in p.dropoffs.iter() {
+                let known = self.structures( p.player_id ).iter().any(|x| x.0 == d.id as i32 );
+                if !known {
+                    let pos = self.pos( d.pos.0, d.pos.1 );
+                    self.set_structure( p.player_id, d.id as i32, pos );
+                    events.push( MapEvent::DropoffBuilt { player: p.player_id, id: d.id as i32, pos: d.pos } );
+                }
+            }
+        }
+
+        for u in frame.map_updates.iter() {
+            let p = self.pos( u.pos.0, u.pos.1 );
+            self.set_halite( p, u.halite );
+        }
+
+        events
+    }
+
+    fn index( & self, p: Position ) -> usize {
+        ( p.y * self.dim.1 + p.x ) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use hlt::protocol::{DropoffFrame, MapUpdate, PlayerFrame, ShipFrame};
+
+    fn ship( player: usize, id: usize, halite: usize ) -> Unit {
+        Unit::Ship { player, id, halite }
+    }
+
+    #[test]
+    fn positions_wrap_onto_the_map() {
+        let map = GameMap::new( (4,6) );
+        assert_eq!( map.pos( -1, -1 ), map.pos( 3, 5 ) );
+        assert_eq!( map.pos( -1, -1 ).yx(), (3,5) );
+        assert_eq!( map.pos( 9, 13 ).yx(), (1,1) );
+        assert_eq!( map.pos( 4, 6 ), map.pos( 0, 0 ) );
+        let p = map.pos( -5, 20 );
+        assert_eq!( ( p.y(), p.x() ), (3,2) );
+    }
+
+    #[test]
+    fn halite_is_read_and_written_through_wrapped_positions() {
+        let mut map = GameMap::from_halite( &[ vec![ 1, 2, 3 ], vec![ 4, 5, 6 ] ] );
+        assert_eq!( map.dim, (2,3) );
+        assert_eq!( map.halite( map.pos( 1, 2 ) ), 6 );
+        let p = map.pos( -1, -1 );
+        map.set_halite( p, 60 );
+        assert_eq!( map.halite( map.pos( 1, 2 ) ), 60 );
+        assert_eq!( map.total_halite(), 75 );
+    }
+
+    #[test]
+    fn ship_index_follows_the_cells() {
+        let mut map = GameMap::new( (8,8) );
+        let (a, b, c) = ( map.pos( 1, 1 ), map.pos( 2, 2 ), map.pos( 5, 5 ) );
+        map.set_ship( a, ship( 0, 3, 10 ) );
+        map.set_ship( b, ship( 0, 1, 20 ) );
+        map.set_ship( c, ship( 1, 2, 30 ) );
+
+        assert_eq!( map.ships_of( 0 ), vec![ (1,b), (3,a) ] );
+        assert_eq!( map.all_ships(), vec![ (0,1,b), (0,3,a), (1,2,c) ] );
+        assert_eq!( map.find_ship( 0, 3 ).map(|x| (x.0.yx(), x.1) ), Some( ((1,1),10) ) );
+        assert!( map.is_occupied( a ) );
+
+        //moving a ship is taking it off one cell and putting it on another
+        map.remove_ship( a );
+        let d = map.pos( 1, 2 );
+        map.set_ship( d, ship( 0, 3, 9 ) );
+        assert!( !map.is_occupied( a ) );
+        assert_eq!( map.ships_of( 0 ), vec![ (1,b), (3,d) ] );
+        assert_eq!( map.find_ship( 0, 3 ).map(|x| (x.0.yx(), x.1) ), Some( ((1,2),9) ) );
+
+        map.remove_ship( c );
+        assert!( map.ships_of( 1 ).is_empty() );
+        assert_eq!( map.find_ship( 1, 2 ), None );
+        assert!( map.ships_of( 7 ).is_empty() );
+    }
+
+    #[test]
+    fn structures_are_listed_shipyard_first_in_id_order() {
+        let mut map = GameMap::new( (8,8) );
+        let (a, b, c) = ( map.pos( 1, 1 ), map.pos( 4, 4 ), map.pos( 6, 2 ) );
+        map.set_structure( 0, 2, b );
+        map.set_structure( 0, -1, a );
+        map.set_structure( 0, 0, c );
+        assert_eq!( map.structures( 0 ), &[ (-1,a), (0,c), (2,b) ] );
+        assert!( matches!( map.structure( b ), Some(Player(0)) ) );
+        assert!( map.structure( map.pos( 0, 0 ) ).is_none() );
+        assert!( map.structures( 1 ).is_empty() );
+    }
+
+    type Ships<'a> = &'a [(usize,(i32,i32),usize)]; //(id, (y,x), cargo)
+
+    //frame of two players with their ships and the (id, (y,x)) of their dropoffs
+    fn frame( turn_num: usize, ships: [Ships;2], dropoffs: [&[(usize,(i32,i32))];2], map_updates: &[((i32,i32),usize)] ) -> TurnFrame {
+        TurnFrame {
+            turn_num,
+            players: (0..2).map(|p| PlayerFrame {
+                player_id: p,
+                halite: 5000,
+                ships: ships[p].iter().map(|&(id,pos,halite)| ShipFrame { id, pos, halite } ).collect(),
+                dropoffs: dropoffs[p].iter().map(|&(id,pos)| DropoffFrame { id, pos } ).collect(),
+            }).collect(),
+            map_updates: map_updates.iter().map(|&(pos,halite)| MapUpdate { pos, halite } ).collect(),
+        }
+    }
+
+    #[test]
+    fn frames_report_spawns_moves_cargo_and_losses() {
+        let mut map = GameMap::new( (8,8) );
+
+        let events = map.apply_frame( &frame( 1, [ &[ (0,(1,1),0) ], &[ (1,(5,5),0) ] ], [ &[], &[] ], &[] ) );
+        assert_eq!( events, vec![
+            MapEvent::ShipSpawned { player: 0, id: 0, pos: (1,1), halite: 0 },
+            MapEvent::ShipSpawned { player: 1, id: 1, pos: (5,5), halite: 0 },
+        ] );
+
+        let events = map.apply_frame( &frame( 2, [ &[ (0,(1,2),0) ], &[ (1,(5,5),10) ] ], [ &[], &[] ], &[ ((5,5),30) ] ) );
+        assert_eq!( events, vec![
+            MapEvent::ShipMoved { player: 0, id: 0, from: (1,1), to: (1,2) },
+            MapEvent::CargoChanged { player: 1, id: 1, from: 0, to: 10 },
+        ] );
+        assert!( !map.is_occupied( map.pos( 1, 1 ) ) );
+        assert_eq!( map.halite( map.pos( 5, 5 ) ), 30 );
+
+        let events = map.apply_frame( &frame( 3, [ &[ (0,(1,2),0) ], &[] ], [ &[], &[] ], &[] ) );
+        assert_eq!( events, vec![ MapEvent::ShipDestroyed { player: 1, id: 1, pos: (5,5), halite: 10 } ] );
+        assert!( !map.is_occupied( map.pos( 5, 5 ) ) );
+        assert!( map.ships_of( 1 ).is_empty() );
+
+        //nothing changed, nothing to report
+        assert!( map.apply_frame( &frame( 4, [ &[ (0,(1,2),0) ], &[] ], [ &[], &[] ], &[] ) ).is_empty() );
+    }
+
+    #[test]
+    fn converted_ship_is_destroyed_and_a_dropoff_built() {
+        let mut map = GameMap::new( (8,8) );
+        map.apply_frame( &frame( 1, [ &[ (0,(1,2),40) ], &[] ], [ &[], &[] ], &[] ) );
+        let events = map.apply_frame( &frame( 2, [ &[], &[] ], [ &[ (0,(1,2)) ], &[] ], &[] ) );
+        assert_eq!( events, vec![
+            MapEvent::ShipDestroyed { player: 0, id: 0, pos: (1,2), halite: 40 },
+            MapEvent::DropoffBuilt { player: 0, id: 0, pos: (1,2) },
+        ] );
+        assert_eq!( map.structures( 0 ), &[ (0,map.pos( 1, 2 )) ] );
+        //a dropoff is only built once
+        assert!( map.apply_frame( &frame( 3, [ &[], &[] ], [ &[ (0,(1,2)) ], &[] ], &[] ) ).is_empty() );
+    }
+
+    #[test]
+    fn ship_moves_into_a_cell_vacated_the_same_turn() {
+        let mut map = GameMap::new( (8,8) );
+        map.apply_frame( &frame( 1, [ &[ (2,(3,3),0), (3,(3,4),0) ], &[] ], [ &[], &[] ], &[] ) );
+        //ship 2 is listed first and takes the cell ship 3 leaves, which must not wipe ship 2 out
+        let events = map.apply_frame( &frame( 2, [ &[ (2,(3,4),0), (3,(3,5),0) ], &[] ], [ &[], &[] ], &[] ) );
+        assert_eq!( events, vec![
+            MapEvent::ShipMoved { player: 0, id: 2, from: (3,3), to: (3,4) },
+            MapEvent::ShipMoved { player: 0, id: 3, from: (3,4), to: (3,5) },
+        ] );
+        assert_eq!( map.ships_of( 0 ), vec![ (2,map.pos( 3, 4 )), (3,map.pos( 3, 5 )) ] );
+        assert!( !map.is_occupied( map.pos( 3, 3 ) ) );
+
+        //and the two swap back
+        map.apply_frame( &frame( 3, [ &[ (2,(3,5),0), (3,(3,4),0) ], &[] ], [ &[], &[] ], &[] ) );
+        assert_eq!( map.ships_of( 0 ), vec![ (2,map.pos( 3, 5 )), (3,map.pos( 3, 4 )) ] );
+    }
+}
@@ -0,0 +1,266 @@
+use hlt::protocol::MapUpdate;
+use mapping::gamemap::GameMap;
+use metric::torus;
+
+//summed-area tables over the map's halite for constant time halite totals of windows and
+//diamonds around a cell. windows are read off the table of the map itself, diamonds off a table
+//of the map turned by 45 degrees, where a diamond becomes a square: cell (i,j) of the map tiled
+//out by `pad` cells on every side goes to (u,v) = (i + j, i - j + width - 1), the width being
+//that of the tiled map. a turn's updates are applied together and each table is rebuilt from
+//the first row a change reaches
+
+pub struct HaliteSum {
+    pub dim: (i32,i32), //num rows, num columns
+    cells: Vec<Vec<usize>>,
+    table: Vec<Vec<usize>>, //table[y][x]: sum of cells above and left of (y,x), one extra row and column of zeros
+    pad: i32, //largest radius whose diamond doesn't wrap onto itself
+    turned: Vec<Vec<usize>>, //turned[u][v]: halite of the tiled cell there, 0 between cells
+    turned_table: Vec<Vec<usize>>, //summed-area table of turned, one extra row and column of zeros
+}
+
+impl HaliteSum {
+
+    pub fn new( map: &GameMap ) -> HaliteSum {
+        let (h, w) = ( map.dim.0 as usize, map.dim.1 as usize );
+        //a diamond of radius r fits without touching itself while 2r is below both sides
+        let pad = ( map.dim.0.min( map.dim.1 ) - 1 ) / 2;
+        let n = turned_side( map.dim, pad );
+        let mut s = HaliteSum {
+            dim: map.dim,
+            cells: vec![ vec![ 0; w ]; h ],
+            table: vec![ vec![ 0; w + 1 ]; h + 1 ],
+            pad,
+            turned: vec![ vec![ 0; n ]; n ],
+            turned_table: vec![ vec![ 0; n + 1 ]; n + 1 ],
+        };
+        for y in 0..map.dim.0 {
+            for x in 0..map.dim.1 {
+                s.set( y, x, map.halite( map.pos( y, x ) ) );
+            }
+        }
+        s.rebuild( 0, 0 );
+        s
+    }
+
+    pub fn update( & mut self, updates: &[MapUpdate] ) {
+        let mut first : Option<(usize,usize)> = None;
+        for u in updates {
+            let (y, x) = torus::wrap( u.pos, self.dim );
+            if self.cells[y as usize][x as usize] != u.halite {
+                let reached = ( y as usize, self.set( y, x, u.halite ) );
+                first = Some( first.map_or( reached, |f| ( f.0.min( reached.0 ), f.1.min( reached.1 ) ) ) );
+            }
+        }
+        if let Some((y, u)) = first {
+            self.rebuild( y, u );
+        }
+    }
+
+    //halite in the rows x cols window whose top left cell is (y,x), wrapping around the edges
+    pub fn window_sum( & self, y: i32, x: i32, rows: i32, cols: i32 ) -> usize {
+        let rows = rows.min( self.dim.0 );
+        let cols = cols.min( self.dim.1 );
+        let mut total = 0;
+        for (y0, y1) in split( y, rows, self.dim.0 ) {
+            for (x0, x1) in split( x, cols, self.dim.1 ) {
+                total += rect( &self.table, y0, x0, y1, x1 );
+            }
+        }
+        total
+    }
+
+    //halite within wrapped distance r of (y,x), the square of side 2r+1 around the cell's place
+    //in the turned table. a diamond wide enough to wrap onto itself counts every cell once by
+    //summing one single row window per row it spans instead
+    pub fn diamond_sum( & self, y: i32, x: i32, r: i32 ) -> usize {
+        if r < 0 {
+            return 0;
+        }
+        if r > self.pad {
+            let rows = self.dim.0;
+            return (-r..=r).filter(|&dy| torus::canonical_axis( dy, rows ) == dy )
+                .map(|dy| {
+                    let half = r - dy.abs();
+                    self.window_sum( y + dy, x - half, 1, 2 * half + 1 )
+                })
+                .sum();
+        }
+        let (y, x) = torus::wrap( (y,x), self.dim );
+        let (u, v) = self.turn( y + self.pad, x + self.pad );
+        let r = r as usize;
+        rect( &self.turned_table, u - r, v - r, u + r + 1, v + r + 1 )
+    }
+
+    //writes a cell and all its copies in the tiled map, returns the first turned row it reaches
+    fn set( & mut self, y: i32, x: i32, halite: usize ) -> usize {
+        self.cells[y as usize][x as usize] = halite;
+        let (h, w, pad) = ( self.dim.0, self.dim.1, self.pad );
+        let mut first = usize::MAX;
+        for i in copies( y, h, pad ) {
+            for j in copies( x, w, pad ) {
+                let (u, v) = self.turn( i, j );
+                self.turned[u][v] = halite;
+                first = first.min( u );
+            }
+        }
+        first
+    }
+
+    //(u,v) in the turned table of cell (i,j) of the tiled map
+    fn turn( & self, i: i32, j: i32 ) -> (usize,usize) {
+        let tiled_width = self.dim.1 + 2 * self.pad;
+        ( ( i + j ) as usize, ( i - j + tiled_width - 1 ) as usize )
+    }
+
+    //prefix sums of the map from row y on and of the turned map from row u on, the rows above are unchanged
+    fn rebuild( & mut self, y: usize, u: usize ) {
+        prefix_sums( &self.cells, & mut self.table, y );
+        prefix_sums( &self.turned, & mut self.turned_table, u );
+    }
+}
+
+//side of the square turned table for a map of dim tiled out by pad cells on every side
+fn turned_side( dim: (i32,i32), pad: i32 ) -> usize {
+    ( dim.0 + dim.1 + 4 * pad - 1 ) as usize
+}
+
+//coordinates along an axis of length n tiled out by pad cells on both ends that show cell c
+fn copies( c: i32, n: i32, pad: i32 ) -> impl Iterator<Item=i32> {
+    (-1..=1).map( move |k| c + pad + k * n ).filter( move |&i| i >= 0 && i < n + 2 * pad )
+}
+
+//fills table from row `from` on with the summed-area table of values
+fn prefix_sums( values: &[Vec<usize>], table: & mut [Vec<usize>], from: usize ) {
+    for y in from..values.len() {
+        let mut row = 0;
+        for x in 0..values[y].len() {
+            row += values[y][x];
+            table[y+1][x+1] = table[y][x+1] + row;
+        }
+    }
+}
+
+//sum over rows y0..y1 and columns x0..x1 of a summed-area table, end exclusive, no wrapping
+fn rect( table: &[Vec<usize>], y0: usize, x0: usize, y1: usize, x1: usize ) -> usize {
+    table[y1][x1] + table[y0][x0] - table[y0][x1] - table[y1][x0]
+}
+
+//splits a wrapped run of len cells starting at start into at most two unwrapped [begin,end) ranges
+fn split( start: i32, len: i32, n: i32 ) -> Vec<(usize,usize)> {
+    let s = ( start % n + n ) % n;
+    if s + len <= n {
+        vec![ ( s as usize, ( s + len ) as usize ) ]
+    } else {
+        vec![ ( s as usize, n as usize ), ( 0, ( s + len - n ) as usize ) ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::{Rng, SeedableRng, XorShiftRng};
+
+    //odd and even sides so both kinds of wrap are covered
+    const DIM: (i32,i32) = (7,10);
+
+    fn random_map( rng: & mut XorShiftRng ) -> GameMap {
+        let halite = (0..DIM.0).map(|_| (0..DIM.1).map(|_| rng.gen_range( 0, 1000 ) ).collect() ).collect::<Vec<Vec<usize>>>();
+        GameMap::from_halite( &halite )
+    }
+
+    fn brute_window( map: &GameMap, y: i32, x: i32, rows: i32, cols: i32 ) -> usize {
+        let mut total = 0;
+        for dy in 0..rows.min( map.dim.0 ) {
+            for dx in 0..cols.min( map.dim.1 ) {
+                total += map.halite( map.pos( y + dy, x + dx ) );
+            }
+        }
+        total
+    }
+
+    fn brute_diamond( map: &GameMap, y: i32, x: i32, r: i32 ) -> usize {
+        let c = map.pos( y, x ).yx();
+        (0..map.dim.0).flat_map(|py| (0..map.dim.1).map( move |px| (py,px) ) )
+            .filter(|p| torus::wrapped_l1( c, *p, map.dim ) <= r )
+            .map(|p| map.halite( map.pos( p.0, p.1 ) ) )
+            .sum()
+    }
+
+    #[test]
+    fn window_sum_matches_brute_force_across_the_edges() {
+        let mut rng = XorShiftRng::seed_from_u64( 5 );
+        let map = random_map( & mut rng );
+        let sum = HaliteSum::new( &map );
+        for y in -8..9 {
+            for x in -11..12 {
+                for &(rows,cols) in [ (1,1), (2,3), (4,6), (7,10), (9,12) ].iter() {
+                    assert_eq!( sum.window_sum( y, x, rows, cols ), brute_window( &map, y, x, rows, cols ), "window {}x{} at ({},{})", rows, cols, y, x );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn diamond_sum_matches_brute_force_for_every_radius() {
+        let mut rng = XorShiftRng::seed_from_u64( 6 );
+        let map = random_map( & mut rng );
+        let sum = HaliteSum::new( &map );
+        for y in 0..DIM.0 {
+            for x in 0..DIM.1 {
+                for r in 0..10 {
+                    assert_eq!( sum.diamond_sum( y, x, r ), brute_diamond( &map, y, x, r ), "radius {} at ({},{})", r, y, x );
+                }
+            }
+        }
+        assert_eq!( sum.diamond_sum( -1, 12, 3 ), brute_diamond( &map, 6, 2, 3 ) );
+    }
+
+    #[test]
+    fn updates_are_seen_by_the_next_query() {
+        let mut rng = XorShiftRng::seed_from_u64( 7 );
+        let mut map = random_map( & mut rng );
+        let mut sum = HaliteSum::new( &map );
+        for _ in 0..20 {
+            let updates = (0..rng.gen_range( 1, 6 )).map(|_| {
+                MapUpdate { pos: ( rng.gen_range( 0, DIM.0 ), rng.gen_range( 0, DIM.1 ) ), halite: rng.gen_range( 0, 1000 ) }
+            }).collect::<Vec<_>>();
+            for u in updates.iter() {
+                let p = map.pos( u.pos.0, u.pos.1 );
+                map.set_halite( p, u.halite );
+            }
+            sum.update( &updates );
+            assert_eq!( sum.window_sum( 0, 0, DIM.0, DIM.1 ), map.total_halite() );
+            for y in 0..DIM.0 {
+                for x in 0..DIM.1 {
+                    for r in 0..5 {
+                        assert_eq!( sum.diamond_sum( y, x, r ), brute_diamond( &map, y, x, r ) );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn diamonds_on_a_square_map_with_changes_at_the_corners() {
+        let mut rng = XorShiftRng::seed_from_u64( 8 );
+        let halite = (0..16).map(|_| (0..16).map(|_| rng.gen_range( 0, 1000 ) ).collect() ).collect::<Vec<Vec<usize>>>();
+        let mut map = GameMap::from_halite( &halite );
+        let mut sum = HaliteSum::new( &map );
+        let updates = [ MapUpdate { pos: (0,0), halite: 5 }, MapUpdate { pos: (15,15), halite: 7 }, MapUpdate { pos: (-1,3), halite: 11 } ];
+        for u in updates.iter() {
+            let p = map.pos( u.pos.0, u.pos.1 );
+            map.set_halite( p, u.halite );
+        }
+        sum.update( &updates );
+        for y in 0..16 {
+            for x in 0..16 {
+                for r in 0..10 {
+                    assert_eq!( sum.diamond_sum( y, x, r ), brute_diamond( &map, y, x, r ), "radius {} at ({},{})", r, y, x );
+                }
+            }
+        }
+        assert_eq!( sum.diamond_sum( 3, 3, -1 ), 0 );
+        assert_eq!( sum.diamond_sum( 3, 3, 16 ), map.total_halite() );
+    }
+}
@@ -0,0 +1,232 @@
+use hlt::protocol::MapUpdate;
+use mapping::gamemap::GameMap;
+use mapping::mapraw::Unit;
+use metric::torus;
+use std::collections::{HashMap, VecDeque};
+
+//remembers how the halite of every cell developed: the recent change log per cell, which player's
+//ship was mining a cell when it dropped, and how fast square regions of the map are drained
+
+//side length of a depletion region
+pub const REGION: i32 = 8;
+//number of turns the depletion rate is averaged over
+const RATE_WINDOW: usize = 20;
+//changes kept per cell, the oldest are dropped first
+const CELL_CHANGES: usize = 32;
+
+pub struct HaliteHistory {
+    pub dim: (i32,i32), //num rows, num columns
+    cells: Vec<VecDeque<(usize,usize)>>, //row major, (turn, halite) of the last CELL_CHANGES changes of the cell
+    last_miner: Vec<Option<(usize,usize)>>, //row major, (player id, turn) of the last ship seen mining the cell
+    mined_by: HashMap<usize,usize>, //player id -> halite taken from cells its ships sat on
+    region_totals: Vec<VecDeque<usize>>, //region -> halite in the region for the last RATE_WINDOW+1 turns
+    total: usize,
+}
+
+impl HaliteHistory {
+
+    pub fn new( map: &GameMap ) -> HaliteHistory {
+        let dim = map.dim;
+        let halite = |y: i32, x: i32| map.halite( map.pos( y, x ) );
+        let regions = ( region_count( dim.0 ) * region_count( dim.1 ) ) as usize;
+        let mut h = HaliteHistory {
+            dim,
+            cells: (0..dim.0).flat_map(|y| (0..dim.1).map( move |x| vec![ (0, halite( y, x )) ].into_iter().collect() ) ).collect(),
+            last_miner: vec![ None; ( dim.0 * dim.1 ) as usize ],
+            mined_by: HashMap::new(),
+            region_totals: vec![ VecDeque::new(); regions ],
+            total: map.total_halite(),
+        };
+        let mut totals = vec![ 0; regions ];
+        for y in 0..dim.0 {
+            for x in 0..dim.1 {
+                totals[ h.region( y, x ) ] += halite( y, x );
+            }
+        }
+        for (q,t) in h.region_totals.iter_mut().zip( totals ) {
+            q.push_back( t );
+        }
+        h
+    }
+
+    //records the turn's map updates, map holds the ships as of the same frame
+    pub fn record_turn( & mut self, turn: usize, updates: &[MapUpdate], map: &GameMap ) {
+
+        let mut totals = self.region_totals.iter().map(|q| *q.back().unwrap_or(&0) ).collect::<Vec<_>>();
+
+        for u in updates {
+            let i = self.index( u.pos );
+            let before = self.cells[i].back().map_or( 0, |x| x.1 );
+            if before == u.halite {
+                continue;
+            }
+            self.cells[i].push_back( (turn, u.halite) );
+            if self.cells[i].len() > CELL_CHANGES {
+                self.cells[i].pop_front();
+            }
+
+            let r = self.region( u.pos.0, u.pos.1 );
+            totals[r] = totals[r] + u.halite - before;
+            self.total = self.total + u.halite - before;
+
+            //halite only leaves a cell by being mined, and the miner stays on the cell doing it
+            if u.halite < before {
+                if let Unit::Ship { player, .. } = map.ship( map.pos( u.pos.0, u.pos.1 ) ) {
+                    self.last_miner[i] = Some( (player, turn) );
+                    *self.mined_by.entry( player ).or_insert(0) += before - u.halite;
+                }
+            }
+        }
+
+        for (q,t) in self.region_totals.iter_mut().zip( totals ) {
+            q.push_back( t );
+            if q.len() > RATE_WINDOW + 1 {
+                q.pop_front();
+            }
+        }
+    }
+
+    //(turn, halite) at the recent changes of the cell oldest first, starting with the initial
+    //amount at turn 0 until the cell changed more than CELL_CHANGES times
+    pub fn cell_history( & self, y: i32, x: i32 ) -> &VecDeque<(usize,usize)> {
+        &self.cells[ self.index( (y,x) ) ]
+    }
+
+    //halite taken so far by the ships of a player
+    pub fn mined_by( & self, player: usize ) -> usize {
+        self.mined_by.get( &player ).cloned().unwrap_or(0)
+    }
+
+    //cells some other player's ships mined within the last `turns` turns
+    pub fn enemy_mining( & self, me: usize, now: usize, turns: usize ) -> Vec<((i32,i32),usize)> {
+        self.last_miner.iter().enumerate()
+            .filter_map(|(i,m)| m.filter(|m| m.0 != me && m.1 + turns >= now ).map(|m| ( ( i as i32 / self.dim.1, i as i32 % self.dim.1 ), m.0 ) ) )
+            .collect()
+    }
+
+    //average halite per turn drained from the region holding (y,x) over the recent turns
+    pub fn depletion_rate( & self, y: i32, x: i32 ) -> f64 {
+        rate( &self.region_totals[ self.region( y, x ) ] )
+    }
+
+    pub fn total( & self ) -> usize {
+        self.total
+    }
+
+    //halite left at game end if the map keeps being drained at the recent rate
+    pub fn projected_remaining( & self, turns_left: usize ) -> usize {
+        let drain : f64 = self.region_totals.iter().map( rate ).sum();
+        ( self.total as f64 - drain * turns_left as f64 ).max( 0. ) as usize
+    }
+
+    fn region( & self, y: i32, x: i32 ) -> usize {
+        let (y,x) = torus::wrap( (y,x), self.dim );
+        ( y / REGION * region_count( self.dim.1 ) + x / REGION ) as usize
+    }
+
+    fn index( & self, p: (i32,i32) ) -> usize {
+        let (y,x) = torus::wrap( p, self.dim );
+        ( y * self.dim.1 + x ) as usize
+    }
+}
+
+fn region_count( len: i32 ) -> i32 {
+    ( len + REGION - 1 ) / REGION
+}
+
+fn rate( totals: &VecDeque<usize> ) -> f64 {
+    match ( totals.front(), totals.back() ) {
+        (Some(first), Some(last)) if totals.len() > 1 => ( *first as f64 - *last as f64 ).max( 0. ) / ( totals.len() - 1 ) as f64,
+        _ => 0.,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    //16x16 cells of 100 halite, four depletion regions
+    fn map() -> GameMap {
+        GameMap::from_halite( &vec![ vec![ 100; 16 ]; 16 ] )
+    }
+
+    fn update( y: i32, x: i32, halite: usize ) -> MapUpdate {
+        MapUpdate { pos: (y,x), halite }
+    }
+
+    #[test]
+    fn halite_taken_under_a_ship_counts_for_its_player() {
+        let mut map = map();
+        let p = map.pos( 2, 3 );
+        map.set_ship( p, Unit::Ship { player: 1, id: 4, halite: 0 } );
+        let p = map.pos( 10, 10 );
+        map.set_ship( p, Unit::Ship { player: 0, id: 5, halite: 0 } );
+        let mut h = HaliteHistory::new( &map );
+
+        h.record_turn( 1, &[ update( 2, 3, 75 ), update( 10, 10, 90 ), update( 5, 5, 60 ) ], &map );
+
+        assert_eq!( h.mined_by( 1 ), 25 );
+        assert_eq!( h.mined_by( 0 ), 10 );
+        assert_eq!( h.mined_by( 2 ), 0 );
+        assert_eq!( h.total(), 16 * 16 * 100 - 75 );
+        assert_eq!( h.cell_history( 2, 3 ).iter().cloned().collect::<Vec<_>>(), vec![ (0,100), (1,75) ] );
+        assert_eq!( h.cell_history( 5, 5 ).iter().cloned().collect::<Vec<_>>(), vec![ (0,100), (1,60) ] );
+    }
+
+    #[test]
+    fn enemy_mining_covers_the_recent_turns_of_other_players() {
+        let mut map = map();
+        let p = map.pos( 2, 3 );
+        map.set_ship( p, Unit::Ship { player: 1, id: 4, halite: 0 } );
+        let p = map.pos( 10, 10 );
+        map.set_ship( p, Unit::Ship { player: 0, id: 5, halite: 0 } );
+        let mut h = HaliteHistory::new( &map );
+
+        h.record_turn( 1, &[ update( 2, 3, 75 ), update( 10, 10, 90 ) ], &map );
+
+        assert_eq!( h.enemy_mining( 0, 1, 5 ), vec![ ((2,3),1) ] );
+        assert_eq!( h.enemy_mining( 0, 6, 5 ), vec![ ((2,3),1) ] );
+        assert!( h.enemy_mining( 0, 7, 5 ).is_empty() );
+        assert_eq!( h.enemy_mining( 1, 1, 5 ), vec![ ((10,10),0) ] );
+    }
+
+    #[test]
+    fn cell_history_keeps_the_latest_changes() {
+        let map = map();
+        let mut h = HaliteHistory::new( &map );
+        for turn in 1..=40 {
+            h.record_turn( turn, &[ update( 7, 7, 100 - turn ) ], &map );
+        }
+        //no change, no entry
+        h.record_turn( 41, &[ update( 7, 7, 60 ) ], &map );
+
+        let cell = h.cell_history( 7, 7 );
+        assert_eq!( cell.len(), CELL_CHANGES );
+        assert_eq!( cell.front(), Some( &(9, 91) ) );
+        assert_eq!( cell.back(), Some( &(40, 60) ) );
+        assert_eq!( h.cell_history( -9, 23 ), cell );
+    }
+
+    #[test]
+    fn depletion_rate_and_projection_follow_the_recent_drain() {
+        let map = map();
+        let mut h = HaliteHistory::new( &map );
+        for turn in 1..=4 {
+            h.record_turn( turn, &[ update( 0, 0, 100 - 10 * turn ) ], &map );
+        }
+        assert_eq!( h.depletion_rate( 3, 5 ), 10. );
+        assert_eq!( h.depletion_rate( 12, 12 ), 0. );
+        //(-1,-1) wraps into the region of (15,15)
+        assert_eq!( h.depletion_rate( -1, -1 ), 0. );
+        assert_eq!( h.total(), 16 * 16 * 100 - 40 );
+        assert_eq!( h.projected_remaining( 100 ), 16 * 16 * 100 - 40 - 1000 );
+        assert_eq!( h.projected_remaining( 10_000 ), 0 );
+
+        //the rate only looks back RATE_WINDOW turns
+        for turn in 5..=( 5 + RATE_WINDOW ) {
+            h.record_turn( turn, &[], &map );
+        }
+        assert_eq!( h.depletion_rate( 3, 5 ), 0. );
+    }
+}
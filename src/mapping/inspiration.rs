@@ -0,0 +1,134 @@
+use hlt::constants::Constants;
+use mapping::gamemap::GameMap;
+use metric::torus;
+use std::collections::HashMap;
+
+//per turn map of where a player's ship would be inspired: at least inspiration_ship_count
+//enemy ships within inspiration_radius, which changes the extract ratio and adds a bonus
+
+pub struct InspirationMap {
+    pub dim: (i32,i32), //num rows, num columns
+    inspired: HashMap<usize,Vec<bool>>, //player id -> row major flag per cell
+    extract_ratio: usize,
+    inspired_extract_ratio: usize,
+    inspired_bonus_multiplier: f64,
+}
+
+impl InspirationMap {
+
+    pub fn new( map: &GameMap, constants: &Constants, players: &[usize] ) -> InspirationMap {
+
+        let dim = map.dim;
+        let len = ( dim.0 * dim.1 ) as usize;
+        let index = |p: (i32,i32)| ( p.0 * dim.1 + p.1 ) as usize;
+
+        //ships in range of each cell, in total and per owner
+        let mut total = vec![ 0usize; len ];
+        let mut own : HashMap<usize,Vec<usize>> = players.iter().map(|p| (*p, vec![ 0usize; len ]) ).collect();
+
+        if constants.inspiration_enabled {
+            for (player, _, pos) in map.all_ships() {
+                let counts = own.entry( player ).or_insert_with(|| vec![ 0usize; len ] );
+                for p in torus::diamond( pos.yx(), constants.inspiration_radius as i32, dim ) {
+                    total[ index( p ) ] += 1;
+                    counts[ index( p ) ] += 1;
+                }
+            }
+        }
+
+        let inspired = players.iter().map(|p| {
+            let flags = total.iter().zip( own[p].iter() )
+                .map(|(t,o)| constants.inspiration_enabled && t - o >= constants.inspiration_ship_count )
+                .collect();
+            (*p, flags)
+        }).collect();
+
+        InspirationMap {
+            dim,
+            inspired,
+            extract_ratio: constants.extract_ratio,
+            inspired_extract_ratio: constants.inspired_extract_ratio,
+            inspired_bonus_multiplier: constants.inspired_bonus_multiplier,
+        }
+    }
+
+    pub fn is_inspired( & self, player: usize, y: i32, x: i32 ) -> bool {
+        let (y,x) = torus::wrap( (y,x), self.dim );
+        self.inspired.get( &player ).is_some_and(|v| v[ ( y * self.dim.1 + x ) as usize ] )
+    }
+
+    //fraction of the cell's halite a ship of the player gains per turn of mining there
+    pub fn extraction_rate( & self, player: usize, y: i32, x: i32 ) -> f64 {
+        if self.is_inspired( player, y, x ) {
+            ( 1. + self.inspired_bonus_multiplier ) / self.inspired_extract_ratio as f64
+        } else {
+            1. / self.extract_ratio as f64
+        }
+    }
+
+    //extraction rate relative to an uninspired ship, 3x with the default constants
+    pub fn gain_factor( & self, player: usize, y: i32, x: i32 ) -> f64 {
+        self.extraction_rate( player, y, x ) * self.extract_ratio as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use mapping::mapraw::Unit;
+
+    //16x16 map with the (player, (y,x)) of every ship
+    fn map( ships: &[(usize,(i32,i32))] ) -> GameMap {
+        let mut map = GameMap::new( (16,16) );
+        for (id, &(player, pos)) in ships.iter().enumerate() {
+            let p = map.pos( pos.0, pos.1 );
+            map.set_ship( p, Unit::Ship { player, id, halite: 0 } );
+        }
+        map
+    }
+
+    #[test]
+    fn own_ships_dont_inspire() {
+        let map = map( &[ (0,(5,4)), (0,(5,6)), (0,(4,5)) ] );
+        let m = InspirationMap::new( &map, &Constants::default(), &[ 0, 1 ] );
+        assert!( !m.is_inspired( 0, 5, 5 ) );
+        assert!( m.is_inspired( 1, 5, 5 ) );
+    }
+
+    #[test]
+    fn enough_enemies_at_the_radius_inspire_across_the_wrap() {
+        //(0,12) is 4 away from (0,0) over one seam, (14,14) 4 away over both
+        let c = Constants::default();
+        let m = InspirationMap::new( &map( &[ (1,(0,12)), (2,(14,14)) ] ), &c, &[ 0, 1, 2 ] );
+        assert!( m.is_inspired( 0, 0, 0 ) );
+        assert!( m.is_inspired( 0, 16, -16 ) );
+        assert!( !m.is_inspired( 0, 0, 1 ) );
+        //each of the two only has the other as enemy
+        assert!( !m.is_inspired( 1, 0, 0 ) );
+
+        let one = InspirationMap::new( &map( &[ (1,(0,12)) ] ), &c, &[ 0 ] );
+        assert!( !one.is_inspired( 0, 0, 0 ) );
+    }
+
+    #[test]
+    fn disabled_inspiration_inspires_nobody() {
+        let map = map( &[ (1,(5,4)), (1,(5,6)), (2,(4,5)) ] );
+        let c = Constants { inspiration_enabled: false, ..Default::default() };
+        let m = InspirationMap::new( &map, &c, &[ 0 ] );
+        assert!( !m.is_inspired( 0, 5, 5 ) );
+        assert_eq!( m.gain_factor( 0, 5, 5 ), 1. );
+    }
+
+    #[test]
+    fn inspired_ships_gain_three_times_as_much_by_default() {
+        let map = map( &[ (1,(5,4)), (1,(5,6)) ] );
+        let m = InspirationMap::new( &map, &Constants::default(), &[ 0 ] );
+        assert_eq!( m.gain_factor( 0, 5, 5 ), 3. );
+        assert_eq!( m.extraction_rate( 0, 5, 5 ), 0.75 );
+        assert_eq!( m.gain_factor( 0, 12, 12 ), 1. );
+        assert_eq!( m.extraction_rate( 0, 12, 12 ), 0.25 );
+        //players the map wasn't built for are never inspired
+        assert!( !m.is_inspired( 3, 5, 5 ) );
+    }
+}
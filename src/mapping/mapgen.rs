@@ -0,0 +1,164 @@
+use rand::Rng;
+use rand::SeedableRng;
+use rand::XorShiftRng;
+
+//seeded symmetric map generator in the style of the official fractal maps: one tile of
+//layered value noise is generated and mirrored so every player gets the same surroundings
+
+const PERSISTENCE: f64 = 0.7;
+const FACTOR_EXP: f64 = 2.;
+const MIN_CELL_MAX: usize = 900;
+const MAX_CELL_MAX: usize = 1000;
+
+pub struct GeneratedMap {
+    pub halite: Vec<Vec<usize>>, //rows of columns, like the init frame
+    pub shipyards: Vec<(i32,i32)>, //(y,x), indexed by player id
+}
+
+pub fn generate( seed: u64, height: i32, width: i32, num_players: usize ) -> Result< GeneratedMap, & 'static str > {
+
+    if !(32..=64).contains( &height ) || !(32..=64).contains( &width ) {
+        return Err( "map dimensions must be within 32 to 64" );
+    }
+    if height % 2 != 0 || width % 2 != 0 {
+        return Err( "map dimensions must be even" );
+    }
+
+    //tile (num rows, num columns) that gets mirrored
+    let tile_dim = match num_players {
+        2 => (height, width / 2),
+        4 => (height / 2, width / 2),
+        _ => { return Err( "number of players must be 2 or 4" ); },
+    };
+
+    let mut rng = XorShiftRng::seed_from_u64( seed );
+
+    let tile = fractal_tile( & mut rng, tile_dim );
+
+    let mut map = vec![ vec![ 0; width as usize ]; height as usize ];
+    for (y,row) in map.iter_mut().enumerate() {
+        for (x,v) in row.iter_mut().enumerate() {
+            let ty = mirror( y as i32, tile_dim.0, height );
+            let tx = mirror( x as i32, tile_dim.1, width );
+            *v = tile[ty as usize][tx as usize];
+        }
+    }
+
+    //shipyard sits in the middle of each tile, mirrored like the halite
+    let base = ( tile_dim.0 / 2, tile_dim.1 / 2 );
+    let shipyards = match num_players {
+        2 => vec![ base, (base.0, width - 1 - base.1) ],
+        _ => vec![ base, (base.0, width - 1 - base.1), (height - 1 - base.0, base.1), (height - 1 - base.0, width - 1 - base.1) ],
+    };
+
+    //the official engine starts every shipyard on an empty cell
+    for &(y,x) in shipyards.iter() {
+        map[y as usize][x as usize] = 0;
+    }
+
+    Ok( GeneratedMap {
+        halite: map,
+        shipyards,
+    } )
+}
+
+//maps a coordinate of the full map onto the tile, reflecting across the tile edge
+fn mirror( v: i32, tile_len: i32, len: i32 ) -> i32 {
+    if tile_len == len || v < tile_len {
+        v
+    } else {
+        len - 1 - v
+    }
+}
+
+//sum of value noise octaves, each twice as fine and PERSISTENCE times as strong as the last
+fn fractal_tile<R: Rng>( rng: & mut R, dim: (i32,i32) ) -> Vec<Vec<usize>> {
+
+    let (h, w) = ( dim.0 as usize, dim.1 as usize );
+    let mut acc = vec![ vec![ 0f64; w ]; h ];
+
+    let mut cell = h.max( w ) / 2;
+    let mut amplitude = 1.;
+    while cell >= 1 {
+        let grid_h = h / cell + 2;
+        let grid_w = w / cell + 2;
+        let grid = (0..grid_h).map(|_| (0..grid_w).map(|_| rng.gen::<f64>() ).collect::<Vec<_>>() ).collect::<Vec<_>>();
+        for (y,row) in acc.iter_mut().enumerate() {
+            for (x,v) in row.iter_mut().enumerate() {
+                let gy = y as f64 / cell as f64;
+                let gx = x as f64 / cell as f64;
+                let (y0, x0) = ( gy.floor() as usize, gx.floor() as usize );
+                let (fy, fx) = ( smooth( gy - y0 as f64 ), smooth( gx - x0 as f64 ) );
+                let top = grid[y0][x0] * (1. - fx) + grid[y0][x0+1] * fx;
+                let bottom = grid[y0+1][x0] * (1. - fx) + grid[y0+1][x0+1] * fx;
+                *v += amplitude * ( top * (1. - fy) + bottom * fy );
+            }
+        }
+        cell /= 2;
+        amplitude *= PERSISTENCE;
+    }
+
+    let lo = acc.iter().flat_map(|r| r.iter() ).cloned().fold( f64::MAX, f64::min );
+    let hi = acc.iter().flat_map(|r| r.iter() ).cloned().fold( f64::MIN, f64::max );
+    let span = if hi > lo { hi - lo } else { 1. };
+    let cell_max = rng.gen_range( MIN_CELL_MAX, MAX_CELL_MAX + 1 ) as f64;
+
+    //raising the normalized noise to a power keeps halite in clusters with sparse gaps between
+    acc.iter().map(|r| {
+        r.iter().map(|v| ( ( (v - lo) / span ).powf( FACTOR_EXP ) * cell_max ).round() as usize ).collect()
+    }).collect()
+}
+
+fn smooth( t: f64 ) -> f64 {
+    t * t * (3. - 2. * t)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn two_players_mirror_left_to_right() {
+        let g = generate( 7, 32, 40, 2 ).unwrap();
+        let (h, w) = ( 32, 40 );
+        for y in 0..h {
+            for x in 0..w {
+                assert_eq!( g.halite[y][x], g.halite[y][w - 1 - x], "cell (y: {}, x: {})", y, x );
+            }
+        }
+        assert_eq!( g.shipyards, vec![ (16,10), (16,29) ] );
+    }
+
+    #[test]
+    fn four_players_mirror_both_ways() {
+        let g = generate( 7, 48, 48, 4 ).unwrap();
+        let n = 48;
+        for y in 0..n {
+            for x in 0..n {
+                let v = g.halite[y][x];
+                assert_eq!( v, g.halite[y][n - 1 - x], "cell (y: {}, x: {})", y, x );
+                assert_eq!( v, g.halite[n - 1 - y][x], "cell (y: {}, x: {})", y, x );
+            }
+        }
+        assert_eq!( g.shipyards, vec![ (12,12), (12,35), (35,12), (35,35) ] );
+    }
+
+    #[test]
+    fn shipyards_start_empty() {
+        for &players in [ 2, 4 ].iter() {
+            let g = generate( 11, 32, 32, players ).unwrap();
+            assert!( g.halite.iter().flat_map(|r| r.iter() ).any(|v| *v > 0 ) );
+            for &(y,x) in g.shipyards.iter() {
+                assert_eq!( g.halite[y as usize][x as usize], 0 );
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let a = generate( 3, 32, 32, 4 ).unwrap();
+        let b = generate( 3, 32, 32, 4 ).unwrap();
+        assert_eq!( a.halite, b.halite );
+    }
+}
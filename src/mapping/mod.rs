@@ -1 +1,8 @@
 pub mod mapraw;
+pub mod mapgen;
+pub mod gamemap;
+pub mod halitesum;
+pub mod inspiration;
+pub mod distfield;
+pub mod threat;
+pub mod history;
@@ -0,0 +1,131 @@
+use hlt::constants::Constants;
+use mapping::gamemap::GameMap;
+use mapping::mapraw::Unit;
+use metric::torus;
+
+//probability that some enemy ship ends up on a cell next turn. each enemy stays or steps to
+//a neighbour; one that can't pay the move cost stays put, otherwise steps towards its nearest
+//structure get more likely the fuller its cargo and the closer it is to home
+
+pub struct ThreatMap {
+    pub dim: (i32,i32), //num rows, num columns
+    free: Vec<f64>, //probability no enemy ends on the cell
+}
+
+impl ThreatMap {
+
+    pub fn new( map: &GameMap, me: usize, constants: &Constants ) -> ThreatMap {
+
+        let dim = map.dim;
+        let mut free = vec![ 1f64; ( dim.0 * dim.1 ) as usize ];
+
+        for (player, _, at) in map.all_ships().into_iter().filter(|x| x.0 != me ) {
+
+            let homes = map.structures( player ).iter().map(|x| x.1.yx() ).collect::<Vec<_>>();
+            let pos = at.yx();
+
+            let cargo = match map.ship( at ) {
+                Unit::Ship { halite, .. } => halite,
+                Unit::None => continue,
+            };
+
+            let move_cost = map.halite( at ) / constants.move_cost_ratio;
+            let mut outcomes = vec![ (pos, 1f64) ];
+
+            if cargo >= move_cost {
+                let fullness = cargo as f64 / constants.max_halite as f64;
+                let home = homes.iter().cloned().min_by_key(|h| torus::wrapped_l1( pos, *h, dim ) );
+                let (homeward, closeness) = match home {
+                    Some(h) => ( torus::directions_toward( pos, h, dim ), 1. / ( 1 + torus::wrapped_l1( pos, h, dim ) ) as f64 ),
+                    None => ( vec![], 0. ),
+                };
+                //a full ship is more likely to leave its cell than an empty one mining it
+                outcomes[0].1 = 1. + ( 1. - fullness );
+                for d in torus::DIRS.iter() {
+                    let w = if homeward.contains( d ) { 1. + 2. * fullness + closeness } else { 1. };
+                    outcomes.push( ( torus::wrap( ( pos.0 + d.0, pos.1 + d.1 ), dim ), w ) );
+                }
+            }
+
+            let total : f64 = outcomes.iter().map(|x| x.1 ).sum();
+            for (p, w) in outcomes {
+                let i = ( p.0 * dim.1 + p.1 ) as usize;
+                free[i] *= 1. - w / total;
+            }
+        }
+
+        ThreatMap { dim, free }
+    }
+
+    pub fn probability( & self, y: i32, x: i32 ) -> f64 {
+        let (y,x) = torus::wrap( (y,x), self.dim );
+        1. - self.free[ ( y * self.dim.1 + x ) as usize ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const EPS: f64 = 1e-9;
+
+    //8x8 map of empty cells, (player, (y,x), cargo) of every ship
+    fn map( ships: &[(usize,(i32,i32),usize)] ) -> GameMap {
+        let mut map = GameMap::new( (8,8) );
+        for (id, &(player, pos, halite)) in ships.iter().enumerate() {
+            let p = map.pos( pos.0, pos.1 );
+            map.set_ship( p, Unit::Ship { player, id, halite } );
+        }
+        map
+    }
+
+    #[test]
+    fn enemy_that_cant_pay_the_move_stays() {
+        let mut map = map( &[ (1,(4,4),4), (0,(1,1),0) ] );
+        let p = map.pos( 4, 4 );
+        map.set_halite( p, 50 );
+        let t = ThreatMap::new( &map, 0, &Constants::default() );
+        assert!( ( t.probability( 4, 4 ) - 1. ).abs() < EPS );
+        for n in torus::ring( (4,4), 1, map.dim ) {
+            assert_eq!( t.probability( n.0, n.1 ), 0. );
+        }
+        //my own ships are no threat
+        assert_eq!( t.probability( 1, 1 ), 0. );
+        assert_eq!( t.probability( 1, 2 ), 0. );
+    }
+
+    #[test]
+    fn full_ship_heads_home() {
+        let mut map = map( &[ (1,(4,4),1000) ] );
+        let home = map.pos( 4, 7 );
+        map.set_structure( 1, -1, home );
+        let t = ThreatMap::new( &map, 0, &Constants::default() );
+        let east = t.probability( 4, 5 );
+        for &(y,x) in [ (3,4), (5,4), (4,3) ].iter() {
+            assert!( east > t.probability( y, x ) + 0.1, "east {} against ({},{})", east, y, x );
+        }
+        assert!( ( t.probability( 3, 4 ) - t.probability( 4, 3 ) ).abs() < EPS );
+        //a full ship is less likely to stay than to step home
+        assert!( t.probability( 4, 4 ) < east );
+        let total : f64 = torus::diamond( (4,4), 1, map.dim ).map(|p| t.probability( p.0, p.1 ) ).sum();
+        assert!( ( total - 1. ).abs() < EPS );
+    }
+
+    #[test]
+    fn enemies_combine_as_independent_threats() {
+        let c = Constants::default();
+        let a = ThreatMap::new( &map( &[ (1,(2,2),0) ] ), 0, &c );
+        let b = ThreatMap::new( &map( &[ (2,(2,4),300) ] ), 0, &c );
+        let both = ThreatMap::new( &map( &[ (1,(2,2),0), (2,(2,4),300) ] ), 0, &c );
+        //an empty ship without a home stays twice as likely as it takes any one step
+        assert!( ( a.probability( 2, 3 ) - 1. / 6. ).abs() < EPS );
+        for y in 0..8 {
+            for x in 0..8 {
+                let expected = 1. - ( 1. - a.probability( y, x ) ) * ( 1. - b.probability( y, x ) );
+                assert!( ( both.probability( y, x ) - expected ).abs() < EPS, "({},{})", y, x );
+            }
+        }
+        assert!( both.probability( 2, 3 ) > a.probability( 2, 3 ).max( b.probability( 2, 3 ) ) );
+    }
+}
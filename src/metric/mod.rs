@@ -1 +1,2 @@
 pub mod norm;
+pub mod torus;
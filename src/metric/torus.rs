@@ -0,0 +1,126 @@
+use metric::norm::norm_l1;
+
+//geometry on the wrapped map, positions are (y,x) and dim is (num rows, num columns)
+
+//the four unit steps, north, south, west, east
+pub const DIRS: [(i32,i32);4] = [ (-1,0), (1,0), (0,-1), (0,1) ];
+
+//shortest signed step count along one axis of length n
+fn axis_delta( d: i32, n: i32 ) -> i32 {
+    let mut d = d % n;
+    if d > n / 2 {
+        d -= n;
+    }
+    if d < -n / 2 {
+        d += n;
+    }
+    d
+}
+
+//one fixed offset per cell along an axis: within (-n/2, n/2], n/2 itself when the axis is even
+pub fn canonical_axis( d: i32, n: i32 ) -> i32 {
+    let d = ( d % n + n ) % n;
+    if d > n / 2 { d - n } else { d }
+}
+
+//shortest signed (dy,dx) that leads from a to b across the wrap
+pub fn wrapped_delta( a: (i32,i32), b: (i32,i32), dim: (i32,i32) ) -> (i32,i32) {
+    ( axis_delta( b.0 - a.0, dim.0 ), axis_delta( b.1 - a.1, dim.1 ) )
+}
+
+pub fn wrapped_l1( a: (i32,i32), b: (i32,i32), dim: (i32,i32) ) -> i32 {
+    let d = wrapped_delta( a, b, dim );
+    norm_l1( d.0, d.1 )
+}
+
+pub fn wrap( p: (i32,i32), dim: (i32,i32) ) -> (i32,i32) {
+    ( ( p.0 % dim.0 + dim.0 ) % dim.0, ( p.1 % dim.1 + dim.1 ) % dim.1 )
+}
+
+//unit steps that bring a closer to b, the vertical one first, empty when a == b
+pub fn directions_toward( a: (i32,i32), b: (i32,i32), dim: (i32,i32) ) -> Vec<(i32,i32)> {
+    let (dy,dx) = wrapped_delta( a, b, dim );
+    let mut ret = vec![];
+    if dy != 0 {
+        ret.push( (dy.signum(), 0) );
+    }
+    if dx != 0 {
+        ret.push( (0, dx.signum()) );
+    }
+    ret
+}
+
+//cells at exactly wrapped distance r from center, each cell once even when the ring wraps onto itself
+pub fn ring( center: (i32,i32), r: i32, dim: (i32,i32) ) -> impl Iterator<Item=(i32,i32)> {
+    (-r..=r).flat_map( move |dy| {
+        let w = r - dy.abs();
+        let dxs = if w == 0 { vec![ 0 ] } else { vec![ -w, w ] };
+        dxs.into_iter().map( move |dx| (dy,dx) )
+    })
+    .filter( move |&d| canonical_axis( d.0, dim.0 ) == d.0 && canonical_axis( d.1, dim.1 ) == d.1 )
+    .map( move |d| wrap( ( center.0 + d.0, center.1 + d.1 ), dim ) )
+}
+
+//cells within wrapped distance r of center, nearest rings first
+pub fn diamond( center: (i32,i32), r: i32, dim: (i32,i32) ) -> impl Iterator<Item=(i32,i32)> {
+    (0..=r).flat_map( move |i| ring( center, i, dim ) )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn wrap_handles_negative_and_far_coordinates() {
+        assert_eq!( wrap( (-1,-1), (8,6) ), (7,5) );
+        assert_eq!( wrap( (-17,13), (8,6) ), (7,1) );
+        assert_eq!( wrap( (8,6), (8,6) ), (0,0) );
+    }
+
+    #[test]
+    fn delta_at_half_the_width() {
+        //both ways are equally short, the delta keeps the sign of the plain difference so
+        //going back is always the exact opposite
+        let dim = (8,8);
+        assert_eq!( wrapped_delta( (0,0), (4,4), dim ), (4,4) );
+        assert_eq!( wrapped_delta( (4,4), (0,0), dim ), (-4,-4) );
+        assert_eq!( wrapped_delta( (6,6), (2,2), dim ), (-4,-4) );
+        assert_eq!( wrapped_delta( (2,2), (6,6), dim ), (4,4) );
+        assert_eq!( wrapped_l1( (0,0), (4,4), dim ), 8 );
+        assert_eq!( wrapped_l1( (4,4), (0,0), dim ), 8 );
+        //an odd axis has no half way cell
+        assert_eq!( wrapped_delta( (0,0), (0,3), (7,7) ), (0,3) );
+        assert_eq!( wrapped_delta( (0,0), (0,4), (7,7) ), (0,-3) );
+    }
+
+    #[test]
+    fn negative_deltas_take_the_short_way() {
+        let dim = (8,10);
+        assert_eq!( wrapped_delta( (0,1), (0,9), dim ), (0,-2) );
+        assert_eq!( wrapped_delta( (0,9), (0,1), dim ), (0,2) );
+        assert_eq!( wrapped_delta( (7,0), (1,0), dim ), (2,0) );
+        assert_eq!( wrapped_delta( (3,3), (1,2), dim ), (-2,-1) );
+        assert_eq!( wrapped_l1( (7,9), (0,0), dim ), 2 );
+    }
+
+    #[test]
+    fn directions_toward_cross_the_seam() {
+        assert_eq!( directions_toward( (0,0), (7,1), (8,8) ), vec![ (-1,0), (0,1) ] );
+        assert!( directions_toward( (2,2), (2,2), (8,8) ).is_empty() );
+    }
+
+    #[test]
+    fn ring_and_diamond_count_each_cell_once() {
+        let dim = (8,8);
+        assert_eq!( ring( (0,0), 0, dim ).collect::<Vec<_>>(), vec![ (0,0) ] );
+        assert_eq!( ring( (0,0), 1, dim ).count(), 4 );
+        assert!( ring( (0,0), 3, dim ).all(|p| wrapped_l1( (0,0), p, dim ) == 3 ) );
+        //a radius reaching around the whole map covers it exactly once
+        let mut all = diamond( (5,2), 8, dim ).collect::<Vec<_>>();
+        all.sort();
+        all.dedup();
+        assert_eq!( all.len(), 64 );
+        assert_eq!( diamond( (5,2), 8, dim ).count(), 64 );
+    }
+}
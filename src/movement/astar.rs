@@ -0,0 +1,171 @@
+use mapping::gamemap::GameMap;
+use mapping::mapraw::Unit;
+use metric::torus;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+//a* on the wrapped map, every step costs one turn plus the weighted halite burned leaving
+//the cell plus a penalty for entering a cell next to an enemy ship, blocked cells are never entered
+
+//cells taken off the open list before the search gives up, about every cell within 22 steps;
+//a goal further off falls back on the greedy choices of schedule
+const MAX_EXPANSIONS: usize = 1024;
+
+pub struct PathCosts {
+    pub move_cost_ratio: usize,
+    pub halite_weight: f64, //turns one halite of move cost is worth
+    pub enemy_adjacent_penalty: f64, //turns an enemy-adjacent cell is worth
+    pub enemy_adjacent: HashSet<(i32,i32)>,
+    pub blocked: HashSet<(i32,i32)>,
+}
+
+impl PathCosts {
+    //enemy ships block their cell and make the cells around them costly
+    pub fn new( map: &GameMap, me: usize, move_cost_ratio: usize ) -> PathCosts {
+        let mut enemy_adjacent = HashSet::new();
+        let mut blocked = HashSet::new();
+        for y in 0..map.dim.0 {
+            for x in 0..map.dim.1 {
+                if let Unit::Ship { player, .. } = map.ship( map.pos( y, x ) ) {
+                    if player != me {
+                        blocked.insert( (y,x) );
+                        for d in torus::DIRS.iter() {
+                            enemy_adjacent.insert( map.pos( y + d.0, x + d.1 ).yx() );
+                        }
+                    }
+                }
+            }
+        }
+        PathCosts {
+            move_cost_ratio,
+            halite_weight: 0.05,
+            enemy_adjacent_penalty: 3.,
+            enemy_adjacent,
+            blocked,
+        }
+    }
+
+    //turns a single move from one cell to its neighbour is worth
+    pub fn step( & self, map: &GameMap, from: (i32,i32), to: (i32,i32) ) -> f64 {
+        let burn = map.halite( map.pos( from.0, from.1 ) ) / self.move_cost_ratio;
+        let penalty = if self.enemy_adjacent.contains( &to ) { self.enemy_adjacent_penalty } else { 0. };
+        1. + self.halite_weight * burn as f64 + penalty
+    }
+}
+
+//path cost ordered by total_cmp so it can key a heap
+pub struct Cost(pub f64);
+
+impl PartialEq for Cost {
+    fn eq( & self, other: &Cost ) -> bool {
+        self.cmp( other ) == Ordering::Equal
+    }
+}
+
+impl Eq for Cost {}
+
+impl PartialOrd for Cost {
+    fn partial_cmp( & self, other: &Cost ) -> Option<Ordering> {
+        Some( self.cmp( other ) )
+    }
+}
+
+impl Ord for Cost {
+    fn cmp( & self, other: &Cost ) -> Ordering {
+        self.0.total_cmp( &other.0 )
+    }
+}
+
+//cells to walk through after from, ending with to, or None when to can't be reached within
+//MAX_EXPANSIONS cells; to itself is never treated as blocked so a path towards an occupied goal still exists
+pub fn find_path( map: &GameMap, from: (i32,i32), to: (i32,i32), costs: &PathCosts ) -> Option<Vec<(i32,i32)>> {
+
+    let from = map.pos( from.0, from.1 ).yx();
+    let to = map.pos( to.0, to.1 ).yx();
+    if from == to {
+        return Some( vec![] );
+    }
+
+    let mut best : HashMap<(i32,i32),f64> = HashMap::new();
+    let mut came_from : HashMap<(i32,i32),(i32,i32)> = HashMap::new();
+    let mut open = BinaryHeap::new();
+
+    best.insert( from, 0. );
+    open.push( Reverse( (Cost( torus::wrapped_l1( from, to, map.dim ) as f64 ), from) ) );
+
+    let mut expanded = 0;
+    while let Some(Reverse((_, p))) = open.pop() {
+        if p == to {
+            let mut path = vec![ to ];
+            let mut cur = to;
+            while let Some(prev) = came_from.get( &cur ) {
+                if *prev == from {
+                    break;
+                }
+                path.push( *prev );
+                cur = *prev;
+            }
+            path.reverse();
+            return Some( path );
+        }
+        expanded += 1;
+        if expanded > MAX_EXPANSIONS {
+            break;
+        }
+        let g = best[&p];
+        for d in torus::DIRS.iter() {
+            let n = map.pos( p.0 + d.0, p.1 + d.1 ).yx();
+            if n != to && costs.blocked.contains( &n ) {
+                continue;
+            }
+            let cost = g + costs.step( map, p, n );
+            if best.get( &n ).is_none_or(|b| cost < *b ) {
+                best.insert( n, cost );
+                came_from.insert( n, p );
+                //every step costs at least a turn, so the wrapped distance never overestimates
+                open.push( Reverse( (Cost( cost + torus::wrapped_l1( n, to, map.dim ) as f64 ), n) ) );
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn costs( blocked: &[(i32,i32)] ) -> PathCosts {
+        PathCosts {
+            move_cost_ratio: 10,
+            halite_weight: 0.05,
+            enemy_adjacent_penalty: 3.,
+            enemy_adjacent: HashSet::new(),
+            blocked: blocked.iter().cloned().collect(),
+        }
+    }
+
+    #[test]
+    fn routes_around_a_blocked_cell_across_the_seam() {
+        let map = GameMap::new( (8,8) );
+        let path = find_path( &map, (0,2), (6,2), &costs( &[ (7,2) ] ) ).unwrap();
+        //the detour over the seam is two steps longer than the straight way, the way back over
+        //the middle of the map would be six
+        assert_eq!( path.len(), 4 );
+        assert_eq!( path.last(), Some( &(6,2) ) );
+        assert!( !path.contains( &(7,2) ) );
+        assert!( path.iter().any(|p| p.0 == 7 ) );
+        let mut prev = (0,2);
+        for &p in path.iter() {
+            assert_eq!( torus::wrapped_l1( prev, p, map.dim ), 1 );
+            prev = p;
+        }
+    }
+
+    #[test]
+    fn walled_off_goal_has_no_path() {
+        let map = GameMap::new( (8,8) );
+        assert!( find_path( &map, (0,2), (4,4), &costs( &[ (3,4), (5,4), (4,3), (4,5) ] ) ).is_none() );
+    }
+}
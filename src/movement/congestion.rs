@@ -0,0 +1,203 @@
+use metric::torus;
+use movement::resolver::Request;
+use std::collections::{HashMap, HashSet};
+
+//traffic rules for the cells next to my structures, applied to the ranked cells of every ship
+//before the moves are resolved: ships only enter a structure from the cells north and south of
+//it and leave onto the cells east and west, a departing ship is held back from a cell a laden
+//returning ship wants next, and a ship that wanted to move near a structure but stayed for
+//GRIDLOCK_TURNS turns in a row is offered every neighbour it may enter and let through ahead
+//of everyone else
+
+const GRIDLOCK_TURNS: usize = 3;
+//distance to a structure within which ships are watched for gridlock
+const WATCH_RADIUS: i32 = 2;
+//cost taken off every option of a gridlocked ship so the assignment clears its way first
+const BREAK_PRIORITY: f64 = 10.;
+
+#[derive(Default)]
+pub struct Congestion {
+    stuck: HashMap<usize,usize>, //ship id -> consecutive turns it wanted to move near a structure and stayed
+}
+
+impl Congestion {
+
+    pub fn new() -> Congestion {
+        Default::default()
+    }
+
+    pub fn is_gridlocked( & self, id: usize ) -> bool {
+        self.stuck.get( &id ).is_some_and(|x| *x >= GRIDLOCK_TURNS )
+    }
+
+    //restricts and reorders the ranked cells of the requests, returning holds the laden ships on
+    //their way to a structure and wants_move the ships with a cell other than their own to go to.
+    //a gridlocked ship is only offered the extra neighbours can_enter( from, cell ) allows, a ship
+    //that stays put keeps its cell whatever its count
+    pub fn regulate<F>( & self, requests: & mut [Request], structures: &[(i32,i32)], returning: &HashSet<usize>, wants_move: &HashSet<usize>, can_enter: F, dim: (i32,i32) )
+        where F: Fn( (i32,i32), (i32,i32) ) -> bool {
+
+        let near = |p: (i32,i32), r: i32| structures.iter().any(|s| torus::wrapped_l1( p, *s, dim ) <= r );
+        let against_lanes = against_lanes( structures, dim );
+        let lane_ok = |from: (i32,i32), to: (i32,i32)| !against_lanes.contains( &(from, to) );
+
+        //where the returning ships close to home head next
+        let wanted = requests.iter()
+            .filter(|r| returning.contains( &r.id ) && near( r.from, WATCH_RADIUS ) )
+            .filter_map(|r| r.candidates.iter()
+                        .filter(|c| c.0 != r.from && lane_ok( r.from, c.0 ) )
+                        .min_by(|a,b| a.1.total_cmp( &b.1 ) )
+                        .map(|c| c.0 ) )
+            .collect::<HashSet<_>>();
+
+        for r in requests.iter_mut() {
+
+            let from = r.from;
+            r.candidates.retain(|c| c.0 == from || lane_ok( from, c.0 ) );
+
+            if wants_move.contains( &r.id ) && self.is_gridlocked( r.id ) {
+                let worst = r.candidates.iter().map(|c| c.1 ).fold( 0., f64::max );
+                let extra = torus::ring( from, 1, dim )
+                    .filter(|n| lane_ok( from, *n ) && can_enter( from, *n ) && !r.candidates.iter().any(|c| c.0 == *n ) )
+                    .collect::<Vec<_>>();
+                r.candidates.extend( extra.into_iter().map(|n| (n, worst + 1.) ) );
+                for c in r.candidates.iter_mut().filter(|c| c.0 != from ) {
+                    c.1 -= BREAK_PRIORITY;
+                }
+                continue;
+            }
+
+            if !returning.contains( &r.id ) && near( from, 1 ) {
+                r.candidates.retain(|c| c.0 == from || !wanted.contains( &c.0 ) );
+            }
+        }
+    }
+
+    //updates the gridlock counts from the turn's outcome, wants_move holds the ships that had a
+    //cell other than their own to go to before the traffic rules were applied
+    pub fn observe( & mut self, requests: &[Request], resolved: &[(usize,(i32,i32))], wants_move: &HashSet<usize>, structures: &[(i32,i32)], dim: (i32,i32) ) {
+        let mut stuck = HashMap::new();
+        for (r, &(id, cell)) in requests.iter().zip( resolved.iter() ) {
+            let near = structures.iter().any(|s| torus::wrapped_l1( r.from, *s, dim ) <= WATCH_RADIUS );
+            if near && cell == r.from && wants_move.contains( &id ) {
+                stuck.insert( id, self.stuck.get( &id ).cloned().unwrap_or(0) + 1 );
+            }
+        }
+        self.stuck = stuck;
+    }
+}
+
+//moves between a structure and its neighbours that go against the lanes: leaving north or south
+//and entering from the east or west
+pub fn against_lanes( structures: &[(i32,i32)], dim: (i32,i32) ) -> HashSet<((i32,i32),(i32,i32))> {
+    let mut moves = HashSet::new();
+    for &s in structures.iter() {
+        for d in [ -1, 1 ].iter() {
+            let inbound = torus::wrap( ( s.0 + d, s.1 ), dim );
+            let outbound = torus::wrap( ( s.0, s.1 + d ), dim );
+            if !structures.contains( &inbound ) {
+                moves.insert( (s, inbound) );
+            }
+            if !structures.contains( &outbound ) {
+                moves.insert( (outbound, s) );
+            }
+        }
+    }
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const DIM: (i32,i32) = (10,10);
+    const SHIPYARD: (i32,i32) = (5,5);
+
+    fn request( id: usize, from: (i32,i32), cells: &[(i32,i32)] ) -> Request {
+        Request { id, from, candidates: cells.iter().enumerate().map(|(i,c)| (*c, i as f64) ).collect() }
+    }
+
+    fn cells( r: &Request ) -> Vec<(i32,i32)> {
+        let mut ret = r.candidates.iter().map(|c| c.0 ).collect::<Vec<_>>();
+        ret.sort();
+        ret
+    }
+
+    fn ids( ids: &[usize] ) -> HashSet<usize> {
+        ids.iter().cloned().collect()
+    }
+
+    //ship 0 stays next to the shipyard for GRIDLOCK_TURNS turns while wanting to move
+    fn gridlocked() -> Congestion {
+        let mut c = Congestion::new();
+        let requests = vec![ request( 0, (4,5), &[ (3,5) ] ) ];
+        for _ in 0..GRIDLOCK_TURNS {
+            assert!( !c.is_gridlocked( 0 ) );
+            c.observe( &requests, &[ (0,(4,5)) ], &ids( &[0] ), &[ SHIPYARD ], DIM );
+        }
+        assert!( c.is_gridlocked( 0 ) );
+        c
+    }
+
+    #[test]
+    fn ships_enter_north_and_south_and_leave_east_and_west() {
+        let mut requests = vec![
+            request( 0, SHIPYARD, &[ (4,5), (6,5), (5,4), (5,6) ] ),
+            request( 1, (5,6), &[ SHIPYARD, (4,6) ] ),
+            request( 2, (4,5), &[ SHIPYARD, (4,4) ] ),
+        ];
+        Congestion::new().regulate( & mut requests, &[ SHIPYARD ], &HashSet::new(), &ids( &[0,1,2] ), |_,_| true, DIM );
+        assert_eq!( cells( &requests[0] ), vec![ (5,4), (5,6) ] );
+        assert_eq!( cells( &requests[1] ), vec![ (4,6) ] );
+        assert_eq!( cells( &requests[2] ), vec![ (4,4), SHIPYARD ] );
+    }
+
+    #[test]
+    fn departing_ship_is_held_back_from_the_cell_a_returning_ship_wants() {
+        let mut requests = vec![
+            request( 0, (5,7), &[ (5,6) ] ),
+            request( 1, SHIPYARD, &[ (5,6), (5,4) ] ),
+        ];
+        Congestion::new().regulate( & mut requests, &[ SHIPYARD ], &ids( &[0] ), &ids( &[0,1] ), |_,_| true, DIM );
+        assert_eq!( cells( &requests[0] ), vec![ (5,6) ] );
+        assert_eq!( cells( &requests[1] ), vec![ (5,4) ] );
+    }
+
+    #[test]
+    fn moving_again_resets_the_gridlock_count() {
+        let mut c = gridlocked();
+        c.observe( &[ request( 0, (4,5), &[ (3,5) ] ) ], &[ (0,(3,5)) ], &ids( &[0] ), &[ SHIPYARD ], DIM );
+        assert!( !c.is_gridlocked( 0 ) );
+    }
+
+    #[test]
+    fn gridlocked_ship_is_offered_the_neighbours_it_may_enter_first() {
+        let c = gridlocked();
+        let mut requests = vec![ request( 0, (4,5), &[ (3,5) ] ), request( 1, (3,4), &[ (3,5) ] ) ];
+        //(4,4) stands for a cell too dangerous to enter
+        c.regulate( & mut requests, &[ SHIPYARD ], &HashSet::new(), &ids( &[0,1] ), |_,n| n != (4,4), DIM );
+        assert_eq!( cells( &requests[0] ), vec![ (3,5), (4,6), SHIPYARD ] );
+        assert!( requests[0].candidates.iter().all(|x| x.1 < 0. ) );
+        assert_eq!( requests[1].candidates, vec![ ((3,5), 0.) ] );
+    }
+
+    #[test]
+    fn gridlocked_ship_keeps_to_the_lanes() {
+        let mut c = Congestion::new();
+        for _ in 0..GRIDLOCK_TURNS {
+            c.observe( &[ request( 0, (5,6), &[ SHIPYARD ] ) ], &[ (0,(5,6)) ], &ids( &[0] ), &[ SHIPYARD ], DIM );
+        }
+        let mut requests = vec![ request( 0, (5,6), &[ SHIPYARD ] ) ];
+        c.regulate( & mut requests, &[ SHIPYARD ], &HashSet::new(), &ids( &[0] ), |_,_| true, DIM );
+        assert_eq!( cells( &requests[0] ), vec![ (4,6), (5,7), (6,6) ] );
+    }
+
+    #[test]
+    fn gridlocked_ship_that_stays_put_keeps_its_cell() {
+        let c = gridlocked();
+        let mut requests = vec![ request( 0, (4,5), &[ (4,5) ] ) ];
+        c.regulate( & mut requests, &[ SHIPYARD ], &HashSet::new(), &HashSet::new(), |_,_| true, DIM );
+        assert_eq!( requests[0].candidates, vec![ ((4,5), 0.) ] );
+    }
+}
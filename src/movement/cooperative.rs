@@ -0,0 +1,193 @@
+use mapping::gamemap::GameMap;
+use metric::torus;
+use movement::astar::{Cost, PathCosts};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+//windowed cooperative a*: ships plan one after the other through space and time, each route is
+//reserved for the next `window` turns and later ships plan around those reservations. past the
+//window the rest of the way is only estimated, so routes are planned afresh every turn
+
+pub type ShipGoal = (usize,(i32,i32),(i32,i32)); //ship id, (y,x) now, (y,x) of the goal
+
+pub struct ReservationTable {
+    pub window: usize,
+    reserved: HashSet<(usize,(i32,i32))>, //(turns from now, cell)
+    shared: HashSet<(i32,i32)>, //cells any number of ships may occupy at once
+    forbidden: HashSet<((i32,i32),(i32,i32))>, //(from, to) moves no route may take
+}
+
+impl ReservationTable {
+
+    pub fn new( window: usize, shared: HashSet<(i32,i32)> ) -> ReservationTable {
+        ReservationTable { window, reserved: HashSet::new(), shared, forbidden: HashSet::new() }
+    }
+
+    pub fn forbid_moves( & mut self, moves: HashSet<((i32,i32),(i32,i32))> ) {
+        self.forbidden.extend( moves );
+    }
+
+    pub fn is_free( & self, t: usize, cell: (i32,i32) ) -> bool {
+        self.shared.contains( &cell ) || !self.reserved.contains( &(t, cell) )
+    }
+
+    //a ship that isn't going anywhere holds its cell for the whole window
+    pub fn reserve_stay( & mut self, cell: (i32,i32) ) {
+        for t in 1..=self.window {
+            self.reserved.insert( (t, cell) );
+        }
+    }
+
+    //path holds the cell for every turn from 1 on, the ship waits on the last one after that
+    pub fn reserve_path( & mut self, from: (i32,i32), path: &[(i32,i32)] ) {
+        let mut last = from;
+        for t in 1..=self.window {
+            if let Some(cell) = path.get( t - 1 ) {
+                last = *cell;
+            }
+            self.reserved.insert( (t, last) );
+        }
+    }
+}
+
+//routes of the ships in priority order, each as the cell the ship occupies in the turns from 1
+//on, ending at the goal or at the end of the window and empty for a ship already at its goal.
+//a ship boxed in by earlier reservations gets no route and waits where it is
+pub fn plan( map: &GameMap, ships: &[ShipGoal], costs: &PathCosts, table: & mut ReservationTable ) -> HashMap<usize,Vec<(i32,i32)>> {
+
+    let mut routes = HashMap::new();
+
+    for &(id, from, to) in ships.iter() {
+        let from = map.pos( from.0, from.1 ).yx();
+        let to = map.pos( to.0, to.1 ).yx();
+        match search( map, from, to, costs, table ) {
+            Some(path) => {
+                table.reserve_path( from, &path );
+                routes.insert( id, path );
+            },
+            None => {
+                table.reserve_stay( from );
+            },
+        }
+    }
+
+    routes
+}
+
+fn search( map: &GameMap, from: (i32,i32), to: (i32,i32), costs: &PathCosts, table: &ReservationTable ) -> Option<Vec<(i32,i32)>> {
+
+    let h = |p: (i32,i32)| torus::wrapped_l1( p, to, map.dim ) as f64;
+
+    let mut best : HashMap<((i32,i32),usize),f64> = HashMap::new();
+    let mut came_from : HashMap<((i32,i32),usize),(i32,i32)> = HashMap::new();
+    let mut open = BinaryHeap::new();
+
+    best.insert( (from, 0), 0. );
+    open.push( Reverse( (Cost( h( from ) ), 0usize, from) ) );
+
+    while let Some(Reverse((_, t, p))) = open.pop() {
+
+        //beyond the window the estimate stands in for the rest of the route
+        if p == to || t == table.window {
+            let mut path = vec![];
+            let mut cur = (p, t);
+            while cur.1 > 0 {
+                path.push( cur.0 );
+                cur = ( came_from[&cur], cur.1 - 1 );
+            }
+            path.reverse();
+            return Some( path );
+        }
+
+        let g = best[&(p,t)];
+        //waiting in place comes first so ties don't make ships wander
+        for d in Some( &(0,0) ).into_iter().chain( torus::DIRS.iter() ) {
+            let n = map.pos( p.0 + d.0, p.1 + d.1 ).yx();
+            if n != p && n != to && costs.blocked.contains( &n ) {
+                continue;
+            }
+            if !table.is_free( t + 1, n ) || table.forbidden.contains( &(p, n) ) {
+                continue;
+            }
+            //waiting costs the turn, moving also the halite burned and enemy proximity
+            let cost = g + if n == p { 1. } else { costs.step( map, p, n ) };
+            if best.get( &(n, t + 1) ).is_none_or(|b| cost < *b ) {
+                best.insert( (n, t + 1), cost );
+                came_from.insert( (n, t + 1), p );
+                open.push( Reverse( (Cost( cost + h( n ) ), t + 1, n) ) );
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const SHIPYARD: (i32,i32) = (5,5);
+
+    fn costs() -> PathCosts {
+        PathCosts {
+            move_cost_ratio: 10,
+            halite_weight: 0.05,
+            enemy_adjacent_penalty: 3.,
+            enemy_adjacent: HashSet::new(),
+            blocked: HashSet::new(),
+        }
+    }
+
+    //cell of the ship t turns from now, the ship waits at the end of its route
+    fn at( from: (i32,i32), route: &[(i32,i32)], t: usize ) -> (i32,i32) {
+        if t == 0 { from } else { route.get( t - 1 ).or( route.last() ).cloned().unwrap_or( from ) }
+    }
+
+    #[test]
+    fn ships_converging_on_a_shipyard_never_share_a_cell() {
+        let map = GameMap::new( (10,10) );
+        let ships = [ (0, (3,5), SHIPYARD), (1, (7,5), SHIPYARD) ];
+        let mut table = ReservationTable::new( 8, HashSet::new() );
+        let routes = plan( &map, &ships, &costs(), & mut table );
+        assert_eq!( routes[&0], vec![ (4,5), SHIPYARD ] );
+        for t in 1..=8 {
+            let a = at( (3,5), &routes[&0], t );
+            let b = at( (7,5), &routes[&1], t );
+            assert_ne!( a, b, "both ships on {:?} at turn {}", a, t );
+        }
+    }
+
+    #[test]
+    fn shared_cell_takes_both_ships() {
+        let map = GameMap::new( (10,10) );
+        let ships = [ (0, (3,5), SHIPYARD), (1, (7,5), SHIPYARD) ];
+        let mut table = ReservationTable::new( 8, vec![ SHIPYARD ].into_iter().collect() );
+        let routes = plan( &map, &ships, &costs(), & mut table );
+        assert_eq!( routes[&0], vec![ (4,5), SHIPYARD ] );
+        assert_eq!( routes[&1], vec![ (6,5), SHIPYARD ] );
+    }
+
+    #[test]
+    fn forbidden_moves_are_routed_around() {
+        let map = GameMap::new( (10,10) );
+        let mut table = ReservationTable::new( 8, HashSet::new() );
+        table.forbid_moves( vec![ ((5,6), SHIPYARD) ].into_iter().collect() );
+        let routes = plan( &map, &[ (0, (5,7), SHIPYARD) ], &costs(), & mut table );
+        assert_eq!( routes[&0].len(), 4 );
+        assert_eq!( routes[&0].last(), Some( &SHIPYARD ) );
+        assert!( !routes[&0].windows( 2 ).any(|w| w[0] == (5,6) && w[1] == SHIPYARD ) );
+    }
+
+    #[test]
+    fn boxed_in_ship_gets_no_route() {
+        let map = GameMap::new( (10,10) );
+        let mut table = ReservationTable::new( 4, HashSet::new() );
+        for n in torus::ring( (2,2), 1, map.dim ) {
+            table.reserve_stay( n );
+        }
+        table.reserve_stay( (2,2) );
+        let routes = plan( &map, &[ (0, (2,2), (8,8)) ], &costs(), & mut table );
+        assert!( !routes.contains_key( &0 ) );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod astar;
+pub mod resolver;
+pub mod cooperative;
+pub mod congestion;
@@ -0,0 +1,182 @@
+use std::collections::{HashMap, HashSet};
+
+//global assignment of ships to the cells they end the turn on: every ship ranks the cells it
+//could reach, staying put included, and the hungarian method finds the assignment with the
+//least total cost in which no two ships share a cell
+
+//cost of pairing a ship with a cell it didn't ask for
+const INFEASIBLE: f64 = 1e9;
+
+pub struct Request {
+    pub id: usize,
+    pub from: (i32,i32),
+    pub candidates: Vec<((i32,i32),f64)>, //cell and cost, lower is better
+}
+
+//cell every ship is given, in request order; a ship that didn't list staying on its own cell
+//may still be left there at a higher cost than any of its candidates.
+//cells in shared take any number of ships, e.g. own structures when ships may crash on them
+pub fn resolve( requests: &[Request], shared: &HashSet<(i32,i32)> ) -> Vec<(usize,(i32,i32))> {
+
+    if requests.is_empty() {
+        return vec![];
+    }
+
+    //one column per cell, shared cells get a column per ship asking for them
+    let mut columns : Vec<(i32,i32)> = vec![];
+    let mut column_of : HashMap<(Option<usize>,(i32,i32)),usize> = HashMap::new();
+    let mut rows : Vec<Vec<(usize,f64)>> = vec![];
+
+    for (i,r) in requests.iter().enumerate() {
+        let worst = r.candidates.iter().map(|c| c.1 ).fold( 0., f64::max );
+        let mut row = vec![];
+        let stay = ( r.from, worst + 1. );
+        for &(cell, cost) in r.candidates.iter().chain( Some( &stay ) ) {
+            let key = if shared.contains( &cell ) { (Some(i), cell) } else { (None, cell) };
+            let j = *column_of.entry( key ).or_insert_with(|| {
+                columns.push( cell );
+                columns.len() - 1
+            });
+            row.push( (j, cost) );
+        }
+        rows.push( row );
+    }
+
+    let mut costs = vec![ vec![ INFEASIBLE; columns.len() ]; requests.len() ];
+    for (i,row) in rows.iter().enumerate() {
+        for &(j, cost) in row.iter() {
+            costs[i][j] = costs[i][j].min( cost );
+        }
+    }
+
+    //ships stand on distinct cells and staying is always a column, so no ship is left out
+    let assignment = hungarian( &costs );
+
+    requests.iter().zip( assignment ).map(|(r,j)| (r.id, columns[j]) ).collect()
+}
+
+//column for every row of a cost matrix with at least as many columns as rows, minimizing the
+//summed cost; the potential based O(rows^2 * columns) formulation
+fn hungarian( costs: &[Vec<f64>] ) -> Vec<usize> {
+
+    let n = costs.len();
+    let m = costs[0].len();
+
+    //1 based, row 0 and column 0 are the sentinels of the augmenting search
+    let mut u = vec![ 0f64; n + 1 ];
+    let mut v = vec![ 0f64; m + 1 ];
+    let mut row_of = vec![ 0usize; m + 1 ];
+    let mut way = vec![ 0usize; m + 1 ];
+
+    for i in 1..=n {
+        row_of[0] = i;
+        let mut j0 = 0;
+        let mut min_v = vec![ f64::INFINITY; m + 1 ];
+        let mut used = vec![ false; m + 1 ];
+        loop {
+            used[j0] = true;
+            let i0 = row_of[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..=m {
+                if used[j] {
+                    continue;
+                }
+                let cur = costs[i0 - 1][j - 1] - u[i0] - v[j];
+                if cur < min_v[j] {
+                    min_v[j] = cur;
+                    way[j] = j0;
+                }
+                if min_v[j] < delta {
+                    delta = min_v[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=m {
+                if used[j] {
+                    u[ row_of[j] ] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_v[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if row_of[j0] == 0 {
+                break;
+            }
+        }
+        //flip the augmenting path
+        while j0 != 0 {
+            let j1 = way[j0];
+            row_of[j0] = row_of[j1];
+            j0 = j1;
+        }
+    }
+
+    let mut column = vec![ 0usize; n ];
+    for j in 1..=m {
+        if row_of[j] != 0 {
+            column[ row_of[j] - 1 ] = j - 1;
+        }
+    }
+    column
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn request( id: usize, from: (i32,i32), candidates: &[((i32,i32),f64)] ) -> Request {
+        Request { id, from, candidates: candidates.to_vec() }
+    }
+
+    #[test]
+    fn ships_that_only_want_each_others_cell_swap() {
+        let requests = vec![
+            request( 0, (0,0), &[ ((0,1), 1.) ] ),
+            request( 1, (0,1), &[ ((0,0), 1.) ] ),
+        ];
+        assert_eq!( resolve( &requests, &HashSet::new() ), vec![ (0,(0,1)), (1,(0,0)) ] );
+    }
+
+    #[test]
+    fn contested_cell_goes_where_the_total_is_least() {
+        //handing ship 0 its favourite first would leave ship 1 staying at 2.2, 3.2 in total,
+        //ship 0 settling for its second choice costs 2.7
+        let requests = vec![
+            request( 0, (0,0), &[ ((0,1), 1.), ((1,0), 1.5) ] ),
+            request( 1, (0,2), &[ ((0,1), 1.2) ] ),
+        ];
+        assert_eq!( resolve( &requests, &HashSet::new() ), vec![ (0,(1,0)), (1,(0,1)) ] );
+    }
+
+    #[test]
+    fn ship_without_a_free_candidate_stays_on_its_cell() {
+        let requests = vec![
+            request( 0, (0,0), &[ ((0,1), 0.), ((1,0), 3.) ] ),
+            request( 1, (0,2), &[ ((0,1), 5.) ] ),
+            request( 2, (3,3), &[] ),
+        ];
+        assert_eq!( resolve( &requests, &HashSet::new() ), vec![ (0,(0,1)), (1,(0,2)), (2,(3,3)) ] );
+    }
+
+    #[test]
+    fn shared_cell_takes_every_ship_asking_for_it() {
+        //the end game crash onto an own structure, ships all deposit on the same turn
+        let requests = vec![
+            request( 0, (4,5), &[ ((5,5), 1.) ] ),
+            request( 1, (6,5), &[ ((5,5), 1.) ] ),
+            request( 2, (5,4), &[ ((5,5), 1.) ] ),
+        ];
+        let shared = vec![ (5,5) ].into_iter().collect();
+        assert_eq!( resolve( &requests, &shared ), vec![ (0,(5,5)), (1,(5,5)), (2,(5,5)) ] );
+        let exclusive = resolve( &requests, &HashSet::new() );
+        assert_eq!( exclusive.iter().filter(|x| x.1 == (5,5) ).count(), 1 );
+    }
+
+    #[test]
+    fn no_requests_no_assignment() {
+        assert!( resolve( &[], &HashSet::new() ).is_empty() );
+    }
+}
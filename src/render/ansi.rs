@@ -0,0 +1,27 @@
+use render::{player_color, Mark, Scene};
+use std::fmt::Write;
+
+//the scene as 24 bit colour terminal text, two characters per cell with the heat as background:
+//a ship shows its owner's id, a structure '##' and a mine target '++' in the owner's colour
+
+pub fn render( scene: &Scene ) -> String {
+
+    let (rows, cols) = scene.dim();
+    let mut out = String::new();
+
+    for y in 0..rows {
+        for x in 0..cols {
+            let (r,g,b) = scene.heat( y, x );
+            let (fg, glyph) = match scene.mark( y, x ) {
+                Mark::Ship( p ) => ( player_color( p ), format!("{:>2}", p ) ),
+                Mark::Structure( p ) => ( player_color( p ), "##".to_string() ),
+                Mark::Target => ( (255,255,255), "++".to_string() ),
+                Mark::Empty => ( (r,g,b), "  ".to_string() ),
+            };
+            write!( out, "\x1b[48;2;{};{};{}m\x1b[38;2;{};{};{}m{}", r, g, b, fg.0, fg.1, fg.2, glyph ).expect("write to string failed");
+        }
+        out.push_str("\x1b[0m\n");
+    }
+
+    out
+}
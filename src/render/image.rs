@@ -0,0 +1,160 @@
+use render::{player_color, Mark, Scene};
+use std::fs::File;
+use std::io::{self, Write};
+
+//rgb raster of a scene, each map cell drawn as a square of cell_px pixels, written as
+//binary ppm or as png; the png is stored uncompressed so no deflate implementation is needed
+
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<(u8,u8,u8)>, //row major
+}
+
+impl Image {
+
+    pub fn from_scene( scene: &Scene, cell_px: usize ) -> Image {
+
+        let (rows, cols) = scene.dim();
+        let cell_px = cell_px.max( 3 );
+        let mut img = Image {
+            width: cols as usize * cell_px,
+            height: rows as usize * cell_px,
+            pixels: vec![ (0,0,0); rows as usize * cols as usize * cell_px * cell_px ],
+        };
+
+        for y in 0..rows {
+            for x in 0..cols {
+                let top = y as usize * cell_px;
+                let left = x as usize * cell_px;
+                img.fill( top, left, cell_px, cell_px, scene.heat( y, x ) );
+                match scene.mark( y, x ) {
+                    Mark::Ship( p ) => img.fill( top + 1, left + 1, cell_px - 2, cell_px - 2, player_color( p ) ),
+                    Mark::Structure( p ) => img.outline( top, left, cell_px, player_color( p ) ),
+                    Mark::Target => {
+                        let mid = cell_px / 2;
+                        img.fill( top + mid, left + 1, 1, cell_px - 2, (255,255,255) );
+                        img.fill( top + 1, left + mid, cell_px - 2, 1, (255,255,255) );
+                    },
+                    Mark::Empty => {},
+                }
+            }
+        }
+
+        img
+    }
+
+    fn fill( & mut self, top: usize, left: usize, h: usize, w: usize, c: (u8,u8,u8) ) {
+        for r in top..top + h {
+            for col in left..left + w {
+                self.pixels[ r * self.width + col ] = c;
+            }
+        }
+    }
+
+    fn outline( & mut self, top: usize, left: usize, size: usize, c: (u8,u8,u8) ) {
+        self.fill( top, left, 1, size, c );
+        self.fill( top + size - 1, left, 1, size, c );
+        self.fill( top, left, size, 1, c );
+        self.fill( top, left + size - 1, size, 1, c );
+    }
+
+    pub fn to_ppm( & self ) -> Vec<u8> {
+        let mut out = format!("P6\n{} {}\n255\n", self.width, self.height ).into_bytes();
+        for &(r,g,b) in self.pixels.iter() {
+            out.extend_from_slice( &[ r, g, b ] );
+        }
+        out
+    }
+
+    pub fn to_png( & self ) -> Vec<u8> {
+
+        //every scanline starts with filter type 0 (none)
+        let mut raw = Vec::with_capacity( self.height * ( 1 + 3 * self.width ) );
+        for row in self.pixels.chunks( self.width.max( 1 ) ) {
+            raw.push( 0 );
+            for &(r,g,b) in row {
+                raw.extend_from_slice( &[ r, g, b ] );
+            }
+        }
+
+        //zlib stream made of stored deflate blocks
+        let mut zlib = vec![ 0x78, 0x01 ];
+        let mut blocks = raw.chunks( 0xFFFF ).peekable();
+        if blocks.peek().is_none() {
+            zlib.extend_from_slice( &[ 1, 0, 0, 0xFF, 0xFF ] );
+        }
+        while let Some(block) = blocks.next() {
+            let len = block.len() as u16;
+            zlib.push( if blocks.peek().is_none() { 1 } else { 0 } );
+            zlib.extend_from_slice( &len.to_le_bytes() );
+            zlib.extend_from_slice( &( !len ).to_le_bytes() );
+            zlib.extend_from_slice( block );
+        }
+        zlib.extend_from_slice( &adler32( &raw ).to_be_bytes() );
+
+        let mut ihdr = vec![];
+        ihdr.extend_from_slice( &( self.width as u32 ).to_be_bytes() );
+        ihdr.extend_from_slice( &( self.height as u32 ).to_be_bytes() );
+        ihdr.extend_from_slice( &[ 8, 2, 0, 0, 0 ] ); //8 bit rgb, no interlace
+
+        let mut out = vec![ 0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n' ];
+        png_chunk( & mut out, b"IHDR", &ihdr );
+        png_chunk( & mut out, b"IDAT", &zlib );
+        png_chunk( & mut out, b"IEND", &[] );
+        out
+    }
+
+    //format follows the extension, ppm unless the path ends in .png
+    pub fn write( & self, path: &str ) -> io::Result<()> {
+        let bytes = if path.to_lowercase().ends_with(".png") { self.to_png() } else { self.to_ppm() };
+        File::create( path )?.write_all( &bytes )
+    }
+}
+
+fn png_chunk( out: & mut Vec<u8>, kind: &[u8;4], data: &[u8] ) {
+    out.extend_from_slice( &( data.len() as u32 ).to_be_bytes() );
+    let start = out.len();
+    out.extend_from_slice( kind );
+    out.extend_from_slice( data );
+    let crc = crc32( &out[start..] );
+    out.extend_from_slice( &crc.to_be_bytes() );
+}
+
+fn crc32( bytes: &[u8] ) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { ( crc >> 1 ) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32( bytes: &[u8] ) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &x in bytes {
+        a = ( a + x as u32 ) % 65521;
+        b = ( b + a ) % 65521;
+    }
+    ( b << 16 ) | a
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn crc32_of_the_end_chunk_type() {
+        //the crc every png ends with
+        assert_eq!( crc32( b"IEND" ), 0xAE42_6082 );
+    }
+
+    #[test]
+    fn adler32_of_a_known_string() {
+        assert_eq!( adler32( b"Wikipedia" ), 0x11E6_0398 );
+        assert_eq!( adler32( b"" ), 1 );
+    }
+}
@@ -0,0 +1,90 @@
+pub mod ansi;
+pub mod image;
+
+use mapping::gamemap::GameMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+//what a rendered frame shows: the halite as a heat map, every player's ships and structures
+//in the player's colour and the mine cells a player's ships were sent to
+
+pub struct Scene<'a> {
+    pub map: &'a GameMap,
+    pub targets: Vec<(i32,i32)>, //(y,x) of assigned mine cells
+    pub max_halite: usize, //halite drawn at full heat, anything above is clamped
+}
+
+//what is drawn on top of a cell's heat, a ship hides the structure it sits on
+pub enum Mark {
+    Ship( usize ), //player id
+    Structure( usize ), //player id
+    Target,
+    Empty,
+}
+
+impl<'a> Scene<'a> {
+
+    pub fn new( map: &'a GameMap, targets: Vec<(i32,i32)>, max_halite: usize ) -> Scene<'a> {
+        Scene { map, targets, max_halite }
+    }
+
+    pub fn dim( & self ) -> (i32,i32) {
+        self.map.dim
+    }
+
+    pub fn mark( & self, y: i32, x: i32 ) -> Mark {
+        use mapping::mapraw::Unit;
+        let p = self.map.pos( y, x );
+        if let Unit::Ship { player, .. } = self.map.ship( p ) {
+            return Mark::Ship( player );
+        }
+        if let Some(p) = self.map.structure( p ) {
+            return Mark::Structure( p.0 );
+        }
+        if self.targets.contains( &(y,x) ) {
+            return Mark::Target;
+        }
+        Mark::Empty
+    }
+
+    //black through dark blue to yellow as the cell fills up
+    pub fn heat( & self, y: i32, x: i32 ) -> (u8,u8,u8) {
+        let v = self.map.halite( self.map.pos( y, x ) ).min( self.max_halite ) as f64 / self.max_halite.max( 1 ) as f64;
+        let v = v.sqrt(); //most cells hold little, stretch the low end
+        ( ( 255. * v ) as u8, ( 220. * v ) as u8, ( 90. * ( 1. - v ) * v * 4. ) as u8 )
+    }
+}
+
+//fixed per player colours, repeating past four players
+pub fn player_color( player: usize ) -> (u8,u8,u8) {
+    const COLORS: [(u8,u8,u8);4] = [ (230,60,60), (60,200,90), (80,140,255), (220,90,230) ];
+    COLORS[ player % COLORS.len() ]
+}
+
+//writes one image and one ansi text file per frame into a directory
+#[derive(Clone)]
+pub struct FrameWriter {
+    dir: String,
+    image_ext: String, //png or ppm
+    cell_px: usize,
+}
+
+impl FrameWriter {
+
+    pub fn new( dir: &str, image_ext: &str ) -> io::Result<FrameWriter> {
+        if image_ext != "png" && image_ext != "ppm" {
+            return Err( io::Error::new( io::ErrorKind::InvalidInput, format!("unknown image format {}", image_ext ) ) );
+        }
+        fs::create_dir_all( dir )?;
+        Ok( FrameWriter { dir: dir.to_string(), image_ext: image_ext.to_string(), cell_px: 8 } )
+    }
+
+    //writes <dir>/<name>.<png|ppm> and <dir>/<name>.ans
+    pub fn write( & self, name: &str, scene: &Scene ) -> io::Result<()> {
+        let base = Path::new( &self.dir ).join( name );
+        let image_path = base.with_extension( &self.image_ext );
+        image::Image::from_scene( scene, self.cell_px ).write( &image_path.to_string_lossy() )?;
+        fs::File::create( base.with_extension("ans") )?.write_all( ansi::render( scene ).as_bytes() )
+    }
+}
@@ -0,0 +1,236 @@
+use hlt::command::{Command, Direction};
+use hlt::constants::Constants;
+use hlt::error::{Error, Result};
+use mapping::gamemap::GameMap;
+use mapping::mapraw::Unit;
+use ruzstd::decoding::StreamingDecoder;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+//reader for the engine's .hlt replays: zstd compressed json, plain json is accepted as well
+
+const ZSTD_MAGIC: [u8;4] = [ 0x28, 0xb5, 0x2f, 0xfd ];
+
+pub struct ReplayPlayer {
+    pub id: usize,
+    pub name: String,
+    pub shipyard: (i32,i32), //(y,x)
+}
+
+#[derive(Clone,Copy,Debug,Default)]
+pub struct PlayerTurnStats {
+    pub halite: usize,
+    pub deposited: usize,
+    pub ships: usize,
+    pub dropoffs: usize, //excluding the shipyard
+}
+
+pub struct ReplayTurn {
+    pub turn_num: usize,
+    pub map: GameMap,
+    pub stats: HashMap<usize,PlayerTurnStats>, //player id -> stats
+    pub commands: HashMap<usize,Vec<Command>>, //player id -> commands issued this turn
+}
+
+pub struct Replay {
+    pub constants: Constants,
+    pub map_seed: u64,
+    pub players: Vec<ReplayPlayer>,
+    pub initial_halite: Vec<Vec<usize>>,
+    pub turns: Vec<ReplayTurn>,
+}
+
+pub fn read<P: AsRef<Path>>( path: P ) -> Result<Replay> {
+    let mut bytes = vec![];
+    File::open( path )?.read_to_end( & mut bytes )?;
+    parse( &bytes )
+}
+
+pub fn parse( bytes: &[u8] ) -> Result<Replay> {
+
+    let json = if bytes.starts_with( &ZSTD_MAGIC ) {
+        let mut decoder = StreamingDecoder::new( bytes ).map_err(|e| invalid( &format!("zstd: {}", e) ) )?;
+        let mut buf = vec![];
+        decoder.read_to_end( & mut buf )?;
+        buf
+    } else {
+        bytes.to_vec()
+    };
+
+    let root : Value = serde_json::from_slice( &json ).map_err(|e| invalid( &format!("json: {}", e) ) )?;
+
+    //constants arrive in the same shape the engine sends them at game start
+    let constants = Constants::new( &field( &root, "GAME_CONSTANTS" )?.to_string() )?;
+    let map_seed = root.get("map_generator_seed").and_then(|x| x.as_u64() ).unwrap_or(0);
+
+    let production = field( &root, "production_map" )?;
+    let height = as_usize( field( production, "height" )? )? as i32;
+    let width = as_usize( field( production, "width" )? )? as i32;
+    let dim = (height, width);
+    let grid = as_array( field( production, "grid" )? )?;
+    if grid.len() != height as usize {
+        return Err( invalid( &format!("production map has {} rows, expected {}", grid.len(), height) ) );
+    }
+    let mut initial_halite = vec![];
+    for row in grid {
+        let row = as_array( row )?;
+        if row.len() != width as usize {
+            return Err( invalid( &format!("production map row has {} cells, expected {}", row.len(), width) ) );
+        }
+        initial_halite.push( row.iter().map(|c| as_usize( field( c, "energy" )? ) ).collect::<Result<Vec<_>>>()? );
+    }
+
+    let mut players = vec![];
+    for p in as_array( field( &root, "players" )? )? {
+        let loc = field( p, "factory_location" )?;
+        players.push( ReplayPlayer {
+            id: as_usize( field( p, "player_id" )? )?,
+            name: p.get("name").and_then(|x| x.as_str() ).unwrap_or("").to_string(),
+            shipyard: location( loc, dim )?,
+        } );
+    }
+
+    //halite and structures carry over from turn to turn, the ships are placed afresh every turn.
+    //shipyards count as dropoffs with id -1, like on the bot's own map
+    let mut terrain = GameMap::from_halite( &initial_halite );
+    for p in players.iter() {
+        let pos = terrain.pos( p.shipyard.0, p.shipyard.1 );
+        terrain.set_structure( p.id, -1, pos );
+    }
+    let mut built : HashMap<usize,usize> = HashMap::new(); //player id -> dropoffs built so far
+    let mut turns = vec![];
+
+    for (turn_num, frame) in as_array( field( &root, "full_frames" )? )?.iter().enumerate() {
+
+        if let Some(cells) = frame.get("cells") {
+            for c in as_array( cells )? {
+                let (y,x) = location( c, dim )?;
+                let pos = terrain.pos( y, x );
+                terrain.set_halite( pos, as_usize( field( c, "production" )? )? );
+            }
+        }
+
+        let mut commands : HashMap<usize,Vec<Command>> = HashMap::new();
+        if let Some(moves) = frame.get("moves").and_then(|x| x.as_object() ) {
+            for (player, cmds) in moves {
+                let player = player_key( player )?;
+                let cmds = as_array( cmds )?.iter().map( command ).collect::<Result<Vec<_>>>()?;
+                commands.insert( player, cmds );
+            }
+        }
+
+        if let Some(events) = frame.get("events") {
+            for e in as_array( events )? {
+                if e.get("type").and_then(|x| x.as_str() ) == Some("construct") {
+                    let owner = as_usize( field( e, "owner_id" )? )?;
+                    let id = as_usize( field( e, "id" )? )?;
+                    let pos = location( field( e, "location" )?, dim )?;
+                    let pos = terrain.pos( pos.0, pos.1 );
+                    terrain.set_structure( owner, id as i32, pos );
+                    *built.entry( owner ).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut map = terrain.clone();
+        let mut stats : HashMap<usize,PlayerTurnStats> = players.iter().map(|p| (p.id, PlayerTurnStats::default()) ).collect();
+
+        if let Some(entities) = frame.get("entities").and_then(|x| x.as_object() ) {
+            for (player, ships) in entities {
+                let player = player_key( player )?;
+                let ships = ships.as_object().ok_or_else(|| invalid("entities of a player is not an object") )?;
+                for (id, ship) in ships {
+                    let id = id.parse::<usize>().map_err(|_| invalid( &format!("ship id '{}'", id) ) )?;
+                    let (y,x) = location( ship, dim )?;
+                    let cargo = as_usize( field( ship, "energy" )? )?;
+                    let pos = map.pos( y, x );
+                    map.set_ship( pos, Unit::Ship { player, id, halite: cargo } );
+                }
+                stats.entry( player ).or_default().ships = ships.len();
+            }
+        }
+
+        if let Some(values) = frame.get("energy").and_then(|x| x.as_object() ) {
+            for (player, v) in values {
+                stats.entry( player_key( player )? ).or_default().halite = as_usize( v )?;
+            }
+        }
+        if let Some(values) = frame.get("deposited").and_then(|x| x.as_object() ) {
+            for (player, v) in values {
+                stats.entry( player_key( player )? ).or_default().deposited = as_usize( v )?;
+            }
+        }
+
+        for (owner, n) in built.iter() {
+            stats.entry( *owner ).or_default().dropoffs = *n;
+        }
+
+        turns.push( ReplayTurn {
+            turn_num,
+            map,
+            stats,
+            commands,
+        } );
+    }
+
+    Ok( Replay {
+        constants,
+        map_seed,
+        players,
+        initial_halite,
+        turns,
+    } )
+}
+
+fn command( v: &Value ) -> Result<Command> {
+    let kind = field( v, "type" )?.as_str().ok_or_else(|| invalid("move type is not a string") )?;
+    match kind {
+        "g" => Ok( Command::Spawn ),
+        "c" => Ok( Command::ConvertToDropoff( as_usize( field( v, "id" )? )? ) ),
+        "m" => {
+            let id = as_usize( field( v, "id" )? )?;
+            let d = field( v, "direction" )?.as_str().unwrap_or("");
+            let mut chars = d.chars();
+            match ( chars.next(), chars.next() ) {
+                ( Some('o'), None ) => Ok( Command::Stay( id ) ),
+                ( Some(c), None ) => Direction::from_char( c )
+                    .map(|dir| Command::Move( id, dir ) )
+                    .ok_or_else(|| invalid( &format!("move direction '{}'", d) ) ),
+                _ => Err( invalid( &format!("move direction '{}'", d) ) ),
+            }
+        },
+        _ => Err( invalid( &format!("move type '{}'", kind) ) ),
+    }
+}
+
+fn location( v: &Value, dim: (i32,i32) ) -> Result<(i32,i32)> {
+    let x = as_usize( field( v, "x" )? )? as i32;
+    let y = as_usize( field( v, "y" )? )? as i32;
+    if y >= dim.0 || x >= dim.1 {
+        return Err( invalid( &format!("location (y: {}, x: {}) outside of {}x{} map", y, x, dim.1, dim.0) ) );
+    }
+    Ok( (y,x) )
+}
+
+fn player_key( key: &str ) -> Result<usize> {
+    key.parse::<usize>().map_err(|_| invalid( &format!("player id '{}'", key) ) )
+}
+
+fn field<'a>( v: &'a Value, key: &str ) -> Result<&'a Value> {
+    v.get( key ).ok_or_else(|| Error::TruncatedFrame( format!("replay field {}", key) ) )
+}
+
+fn as_array( v: &Value ) -> Result<&Vec<Value>> {
+    v.as_array().ok_or_else(|| invalid( &format!("expected array, got {}", v) ) )
+}
+
+fn as_usize( v: &Value ) -> Result<usize> {
+    v.as_u64().map(|x| x as usize ).ok_or_else(|| Error::Parse { token: v.to_string(), expected: "non negative integer".to_string() } )
+}
+
+fn invalid( msg: &str ) -> Error {
+    Error::InvalidFrame( format!("replay: {}", msg) )
+}
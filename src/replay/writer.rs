@@ -0,0 +1,360 @@
+use hlt::command::Command;
+use hlt::constants::Constants;
+use hlt::error::Result;
+use hlt::protocol::{InitFrame, TurnFrame};
+use metric::torus;
+use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+//writes games played through the bot or the simulator in the replay schema of the official
+//viewer, everything is derived from the parsed frames so both sources record the same way
+
+pub struct ReplayWriter {
+    constants: Constants,
+    map_seed: u64,
+    dim: (i32,i32),
+    shipyards: Vec<(usize,(i32,i32))>, //(player id, (y,x))
+    names: HashMap<usize,String>,
+    initial_halite: Vec<Vec<usize>>,
+    frames: Vec<Value>,
+    prev: Option<TurnFrame>,
+    prev_commands: HashMap<usize,Vec<Command>>, //player id -> answers to prev, carried out before the next frame
+    deposited: HashMap<usize,usize>, //player id -> total deposited so far
+}
+
+impl ReplayWriter {
+
+    pub fn new( init: &InitFrame, map_seed: u64 ) -> ReplayWriter {
+        let mut shipyards = init.shipyards.iter().map(|s| (s.player_id, s.pos) ).collect::<Vec<_>>();
+        shipyards.sort();
+        ReplayWriter {
+            constants: init.constants.clone(),
+            map_seed,
+            dim: init.dim,
+            names: shipyards.iter().map(|s| (s.0, format!("player {}", s.0)) ).collect(),
+            shipyards,
+            initial_halite: init.halite.clone(),
+            frames: vec![],
+            prev: None,
+            prev_commands: HashMap::new(),
+            deposited: HashMap::new(),
+        }
+    }
+
+    pub fn set_name( & mut self, player_id: usize, name: &str ) {
+        self.names.insert( player_id, name.to_string() );
+    }
+
+    //records one turn: the frame the players saw and the commands they answered with
+    pub fn record_turn( & mut self, frame: &TurnFrame, commands: &HashMap<usize,Vec<Command>> ) {
+
+        let mut events = vec![];
+        let inspired = inspired_ships( frame, &self.constants, self.dim );
+        let mut energy = Map::new();
+        let mut entities = Map::new();
+
+        for p in frame.players.iter() {
+
+            let prev = self.prev.as_ref().and_then(|f| f.players.iter().find(|x| x.player_id == p.player_id ) );
+            let prev_ships = prev.map(|x| x.ships.iter().map(|s| s.id ).collect::<HashSet<_>>() ).unwrap_or_default();
+            let prev_dropoffs = prev.map(|x| x.dropoffs.iter().map(|d| d.id ).collect::<HashSet<_>>() ).unwrap_or_default();
+
+            let mut ships = Map::new();
+            let mut spawned = 0;
+            for s in p.ships.iter() {
+                if !prev_ships.contains( &s.id ) {
+                    spawned += 1;
+                    events.push( json!({ "type": "spawn", "id": s.id, "owner_id": p.player_id, "energy": 0, "location": location( s.pos ) }) );
+                }
+                ships.insert( s.id.to_string(), json!({ "energy": s.halite, "is_inspired": inspired.contains( &s.id ), "x": s.pos.1, "y": s.pos.0 }) );
+            }
+
+            let mut built = 0;
+            for d in p.dropoffs.iter().filter(|d| !prev_dropoffs.contains( &d.id ) ) {
+                built += 1;
+                events.push( json!({ "type": "construct", "id": d.id, "owner_id": p.player_id, "location": location( d.pos ) }) );
+            }
+
+            //the frames only show the bank, so deposits are what the bank gained beyond spending
+            if let Some(prev) = prev {
+                let spent = spawned * self.constants.ship_cost + built * self.constants.dropoff_cost;
+                let gained = ( p.halite + spent ).saturating_sub( prev.halite );
+                *self.deposited.entry( p.player_id ).or_insert(0) += gained;
+            }
+
+            energy.insert( p.player_id.to_string(), json!( p.halite ) );
+            entities.insert( p.player_id.to_string(), Value::Object( ships ) );
+        }
+
+        //ships that vanished without becoming a dropoff went down in a collision while carrying out
+        //the answers to the previous frame, ships that ended on the same cell sank together
+        if let Some(prev) = self.prev.as_ref() {
+            let alive = frame.players.iter().flat_map(|p| p.ships.iter().map(|s| s.id ) ).collect::<HashSet<_>>();
+            //the commands of the other players are unknown to the bot, a dropoff built this turn
+            //where the ship was tells a conversion apart from a wreck
+            let known = prev.players.iter().flat_map(|p| p.dropoffs.iter().map( move |d| (p.player_id, d.id) ) ).collect::<HashSet<_>>();
+            let new_dropoffs = frame.players.iter()
+                .flat_map(|p| p.dropoffs.iter().map( move |d| (p.player_id, d) ) )
+                .filter(|(player,d)| !known.contains( &(*player, d.id) ) )
+                .map(|(_,d)| d.pos )
+                .collect::<HashSet<_>>();
+            let mut wrecks : HashMap<(i32,i32),Vec<usize>> = HashMap::new();
+            for p in prev.players.iter() {
+                let cmds = self.prev_commands.get( &p.player_id ).map_or( &[][..], |c| &c[..] );
+                for s in p.ships.iter().filter(|s| !alive.contains( &s.id ) && !new_dropoffs.contains( &s.pos ) ) {
+                    let pos = match cmds.iter().find(|c| c.ship_id() == Some( s.id ) ) {
+                        Some(Command::ConvertToDropoff(_)) => continue,
+                        Some(Command::Move(_,dir)) => torus::wrap( ( s.pos.0 + dir.delta().0, s.pos.1 + dir.delta().1 ), self.dim ),
+                        _ => s.pos,
+                    };
+                    wrecks.entry( pos ).or_default().push( s.id );
+                }
+            }
+            let mut wrecks = wrecks.into_iter().collect::<Vec<_>>();
+            wrecks.sort();
+            for (pos, mut ships) in wrecks {
+                ships.sort();
+                events.push( json!({ "type": "shipwreck", "ships": ships, "location": location( pos ) }) );
+            }
+        }
+
+        let cells = frame.map_updates.iter().map(|u| json!({ "x": u.pos.1, "y": u.pos.0, "production": u.halite }) ).collect::<Vec<_>>();
+
+        let mut moves = Map::new();
+        for p in frame.players.iter() {
+            let cmds = commands.get( &p.player_id ).map(|c| c.iter().map( command ).collect::<Vec<_>>() ).unwrap_or_default();
+            moves.insert( p.player_id.to_string(), Value::Array( cmds ) );
+        }
+
+        let deposited = self.deposited.iter().map(|(k,v)| (k.to_string(), json!( v )) ).collect::<Map<_,_>>();
+
+        self.frames.push( json!({
+            "cells": cells,
+            "deposited": deposited,
+            "energy": energy,
+            "entities": entities,
+            "events": events,
+            "moves": moves,
+        }) );
+
+        self.prev = Some( frame.clone() );
+        self.prev_commands = commands.clone();
+    }
+
+    pub fn to_json( & self ) -> Value {
+
+        let final_halite = |id: usize| {
+            self.prev.as_ref()
+                .and_then(|f| f.players.iter().find(|p| p.player_id == id ) )
+                .map_or( self.constants.initial_halite, |p| p.halite )
+        };
+
+        let mut ranked = self.shipyards.iter().map(|s| s.0 ).collect::<Vec<_>>();
+        ranked.sort_by(|a,b| final_halite(*b).cmp( &final_halite(*a) ).then( a.cmp(b) ) );
+
+        let player_statistics = self.shipyards.iter().map(|&(id,_)| {
+            json!({
+                "player_id": id,
+                "rank": ranked.iter().position(|x| *x == id ).unwrap_or(0) + 1,
+                "final_production": final_halite( id ),
+                "total_production": self.deposited.get( &id ).cloned().unwrap_or(0),
+                "last_turn_alive": self.frames.len(),
+            })
+        }).collect::<Vec<_>>();
+
+        let players = self.shipyards.iter().map(|&(id,pos)| {
+            json!({
+                "player_id": id,
+                "name": self.names.get( &id ).cloned().unwrap_or_default(),
+                "energy": self.constants.initial_halite,
+                "entities": [],
+                "factory_location": location( pos ),
+            })
+        }).collect::<Vec<_>>();
+
+        let grid = self.initial_halite.iter().map(|row| {
+            Value::Array( row.iter().map(|v| json!({ "energy": v }) ).collect() )
+        }).collect::<Vec<_>>();
+
+        json!({
+            "ENGINE_VERSION": "1.0",
+            "REPLAY_FILE_VERSION": 3,
+            "GAME_CONSTANTS": constants( &self.constants, self.dim ),
+            "map_generator_seed": self.map_seed,
+            "number_of_players": self.shipyards.len(),
+            "players": players,
+            "production_map": {
+                "map_generator": "fractal",
+                "height": self.dim.0,
+                "width": self.dim.1,
+                "grid": grid,
+            },
+            "full_frames": self.frames,
+            "game_statistics": {
+                "number_turns": self.frames.len(),
+                "player_statistics": player_statistics,
+            },
+        })
+    }
+
+    //zstd compressed like the engine's own .hlt files
+    pub fn write<P: AsRef<Path>>( & self, path: P ) -> Result<()> {
+        let json = self.to_json().to_string();
+        let compressed = compress_to_vec( json.as_bytes(), CompressionLevel::Fastest );
+        File::create( path )?.write_all( &compressed )?;
+        Ok(())
+    }
+}
+
+//ships that have at least inspiration_ship_count enemy ships within the inspiration radius
+fn inspired_ships( frame: &TurnFrame, c: &Constants, dim: (i32,i32) ) -> HashSet<usize> {
+    if !c.inspiration_enabled {
+        return HashSet::new();
+    }
+    let ships = frame.players.iter().flat_map(|p| p.ships.iter().map( move |s| (p.player_id, s.id, s.pos) ) ).collect::<Vec<_>>();
+    ships.iter()
+        .filter(|a| {
+            let enemies = ships.iter().filter(|b| b.0 != a.0 && torus::wrapped_l1( a.2, b.2, dim ) <= c.inspiration_radius as i32 ).count();
+            enemies >= c.inspiration_ship_count
+        })
+        .map(|a| a.1 )
+        .collect()
+}
+
+fn location( pos: (i32,i32) ) -> Value {
+    json!({ "x": pos.1, "y": pos.0 })
+}
+
+fn command( cmd: &Command ) -> Value {
+    match *cmd {
+        Command::Move(id,dir) => json!({ "type": "m", "id": id, "direction": dir.as_char().to_string() }),
+        Command::Stay(id) => json!({ "type": "m", "id": id, "direction": "o" }),
+        Command::ConvertToDropoff(id) => json!({ "type": "c", "id": id }),
+        Command::Spawn => json!({ "type": "g" }),
+    }
+}
+
+//same keys the engine sends at game start and Constants::new reads back
+fn constants( c: &Constants, dim: (i32,i32) ) -> Value {
+    json!({
+        "NEW_ENTITY_ENERGY_COST": c.ship_cost,
+        "DROPOFF_COST": c.dropoff_cost,
+        "MAX_ENERGY": c.max_halite,
+        "MAX_TURNS": c.max_turns,
+        "EXTRACT_RATIO": c.extract_ratio,
+        "MOVE_COST_RATIO": c.move_cost_ratio,
+        "INSPIRATION_ENABLED": c.inspiration_enabled,
+        "INSPIRATION_RADIUS": c.inspiration_radius,
+        "INSPIRATION_SHIP_COUNT": c.inspiration_ship_count,
+        "INSPIRED_EXTRACT_RATIO": c.inspired_extract_ratio,
+        "INSPIRED_BONUS_MULTIPLIER": c.inspired_bonus_multiplier,
+        "INSPIRED_MOVE_COST_RATIO": c.inspired_move_cost_ratio,
+        "INITIAL_ENERGY": c.initial_halite,
+        "DEFAULT_MAP_HEIGHT": dim.0,
+        "DEFAULT_MAP_WIDTH": dim.1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use hlt::command::Direction;
+    use mapping::mapraw::Player;
+    use replay::reader;
+    use sim::engine::Engine;
+
+    #[test]
+    fn written_replay_reads_back_the_same_turns() {
+
+        //below 10 halite a cell costs nothing to leave, so the ships move without mining first
+        let halite = (0..8).map(|y| (0..8).map(|x| ( 3 * y + x ) % 10 ).collect() ).collect();
+        let mut engine = Engine::new( Constants::default(), halite, &[ (1,1), (5,5) ] );
+        let mut w = ReplayWriter::new( &engine.init_frame( 0 ), 17 );
+        let mut halite = engine.halite.clone();
+
+        //spawn, walk the ships towards each other, build a dropoff, then crash two ships of
+        //player 1 into each other on it
+        let turns = [
+            vec![ vec![ Command::Spawn ], vec![ Command::Spawn ] ],
+            vec![ vec![ Command::Move(0,Direction::South) ], vec![ Command::Move(1,Direction::North) ] ],
+            vec![ vec![ Command::Move(0,Direction::East) ], vec![ Command::Stay(1) ] ],
+            vec![ vec![ Command::Stay(0) ], vec![ Command::Move(1,Direction::West) ] ],
+            vec![ vec![ Command::ConvertToDropoff(0) ], vec![] ],
+            vec![ vec![], vec![ Command::Spawn, Command::Move(1,Direction::North) ] ],
+            vec![ vec![], vec![ Command::Move(1,Direction::North), Command::Move(2,Direction::West) ] ],
+            vec![ vec![], vec![ Command::Move(1,Direction::West), Command::Move(2,Direction::West) ] ],
+            vec![ vec![], vec![ Command::Stay(1), Command::Move(2,Direction::West) ] ],
+            vec![ vec![], vec![ Command::Stay(1), Command::Move(2,Direction::North) ] ],
+            vec![ vec![], vec![ Command::Stay(1), Command::Move(2,Direction::North) ] ],
+            vec![ vec![], vec![ Command::Move(1,Direction::West), Command::Move(2,Direction::North) ] ],
+            vec![ vec![], vec![] ],
+        ];
+        let mut frames = vec![];
+        for commands in turns.iter() {
+            let frame = engine.turn_frame();
+            w.record_turn( &frame, &commands.iter().cloned().enumerate().collect() );
+            engine.step( commands );
+            frames.push( frame );
+        }
+
+        //the conversion is no wreck, the crash is one wreck on the dropoff with both ships
+        let wrecks = w.to_json()["full_frames"].as_array().unwrap().iter()
+            .flat_map(|f| f["events"].as_array().unwrap().clone() )
+            .filter(|e| e["type"] == json!( "shipwreck" ) )
+            .collect::<Vec<_>>();
+        assert_eq!( wrecks, vec![ json!({ "type": "shipwreck", "ships": [ 1, 2 ], "location": { "x": 2, "y": 2 } }) ] );
+
+        let path = std::env::temp_dir().join( format!("origin-writer-{}.hlt", std::process::id() ) );
+        w.write( &path ).unwrap();
+        let r = reader::read( &path ).unwrap();
+        std::fs::remove_file( &path ).unwrap();
+
+        assert_eq!( r.map_seed, 17 );
+        assert_eq!( r.constants.initial_halite, 5000 );
+        assert_eq!( r.players.iter().map(|p| p.shipyard ).collect::<Vec<_>>(), vec![ (1,1), (5,5) ] );
+        assert_eq!( r.turns.len(), frames.len() );
+
+        for ((t, frame), commands) in r.turns.iter().zip( frames.iter() ).zip( turns.iter() ) {
+            for u in frame.map_updates.iter() {
+                halite[u.pos.0 as usize][u.pos.1 as usize] = u.halite;
+            }
+            for (y,row) in halite.iter().enumerate() {
+                for (x,v) in row.iter().enumerate() {
+                    assert_eq!( t.map.halite( t.map.pos( y as i32, x as i32 ) ), *v, "halite of turn {}", t.turn_num );
+                }
+            }
+            for p in frame.players.iter() {
+                assert_eq!( t.stats[&p.player_id].halite, p.halite );
+                assert_eq!( t.stats[&p.player_id].ships, p.ships.len() );
+                assert_eq!( t.commands[&p.player_id], commands[p.player_id] );
+                for s in p.ships.iter() {
+                    assert_eq!( t.map.find_ship( p.player_id, s.id ).map(|x| (x.0.yx(), x.1) ), Some( (s.pos, s.halite) ) );
+                }
+                for d in p.dropoffs.iter() {
+                    assert_eq!( t.map.structure( t.map.pos( d.pos.0, d.pos.1 ) ).map(|x| x.0 ), Some( p.player_id ) );
+                }
+            }
+            assert!( matches!( t.map.structure( t.map.pos( 1, 1 ) ), Some(Player(0)) ) );
+        }
+        assert_eq!( r.turns.last().unwrap().stats[&0].dropoffs, 1 );
+    }
+
+    #[test]
+    fn ships_are_inspired_by_nearby_enemies() {
+        let constants = Constants { inspiration_ship_count: 1, ..Default::default() };
+        let mut engine = Engine::new( constants, vec![ vec![ 0; 16 ]; 16 ], &[ (1,1), (3,3), (12,12) ] );
+        let mut w = ReplayWriter::new( &engine.init_frame( 0 ), 0 );
+        engine.step( &[ vec![ Command::Spawn ], vec![ Command::Spawn ], vec![ Command::Spawn ] ] );
+        w.record_turn( &engine.turn_frame(), &HashMap::new() );
+        let json = w.to_json();
+        let entities = &json["full_frames"][0]["entities"];
+        assert_eq!( entities["0"]["0"]["is_inspired"], json!( true ) );
+        assert_eq!( entities["1"]["1"]["is_inspired"], json!( true ) );
+        assert_eq!( entities["2"]["2"]["is_inspired"], json!( false ) );
+    }
+}
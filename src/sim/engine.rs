@@ -0,0 +1,419 @@
+use hlt::command::Command;
+use hlt::constants::Constants;
+use hlt::protocol::{DropoffFrame, InitFrame, MapUpdate, PlayerFrame, ShipFrame, ShipyardFrame, TurnFrame};
+use metric::torus;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+//local stand-in for the halite engine, deterministic for a given map and command stream
+
+#[derive(Clone,Copy,Debug)]
+pub struct SimShip {
+    pub id: usize,
+    pub owner: usize,
+    pub pos: (i32,i32), //(y,x)
+    pub halite: usize,
+    pub inspired: bool,
+}
+
+#[derive(Clone,Debug)]
+pub struct SimPlayer {
+    pub id: usize,
+    pub halite: usize,
+    pub shipyard: (i32,i32), //(y,x)
+    pub dropoffs: Vec<(usize,(i32,i32))>, //(dropoff id, (y,x))
+    pub deposited: usize, //total halite brought home over the game
+}
+
+#[derive(Clone,Debug)]
+pub enum SimEvent {
+    Spawn { owner: usize, id: usize }, //always on the owner's shipyard
+    Construct { owner: usize, id: usize, pos: (i32,i32) },
+    Collision { ships: Vec<usize> }, //ids of the ships that sank together
+}
+
+pub struct Engine {
+    pub constants: Constants,
+    pub dim: (i32,i32), //num rows, num columns
+    pub halite: Vec<Vec<usize>>,
+    pub players: Vec<SimPlayer>,
+    pub ships: BTreeMap<usize,SimShip>, //ship id -> ship
+    pub turn: usize, //number of turns processed so far
+    pub events: Vec<SimEvent>, //what happened during the last processed turn
+    next_ship_id: usize,
+    next_dropoff_id: usize,
+    changed_cells: BTreeSet<(i32,i32)>, //cells changed since the last turn frame
+}
+
+impl Engine {
+
+    pub fn new( constants: Constants, halite: Vec<Vec<usize>>, shipyards: &[(i32,i32)] ) -> Engine {
+        let dim = ( halite.len() as i32, halite.first().map_or( 0, |r| r.len() ) as i32 );
+        let players = shipyards.iter().enumerate().map(|(id,pos)| {
+            SimPlayer { id, halite: constants.initial_halite, shipyard: *pos, dropoffs: vec![], deposited: 0 }
+        }).collect();
+        Engine {
+            constants,
+            dim,
+            halite,
+            players,
+            ships: BTreeMap::new(),
+            turn: 0,
+            events: vec![],
+            next_ship_id: 0,
+            next_dropoff_id: 0,
+            changed_cells: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_finished( & self ) -> bool {
+        self.turn >= self.constants.max_turns
+    }
+
+    pub fn init_frame( & self, player_id: usize ) -> InitFrame {
+        InitFrame {
+            constants: self.constants.clone(),
+            num_players: self.players.len(),
+            my_id: player_id,
+            shipyards: self.players.iter().map(|p| ShipyardFrame { player_id: p.id, pos: p.shipyard } ).collect(),
+            dim: self.dim,
+            halite: self.halite.clone(),
+        }
+    }
+
+    //frame for the upcoming turn, consumes the pending map updates
+    pub fn turn_frame( & mut self ) -> TurnFrame {
+        let players = self.players.iter().map(|p| {
+            PlayerFrame {
+                player_id: p.id,
+                halite: p.halite,
+                ships: self.ships.values().filter(|s| s.owner == p.id ).map(|s| ShipFrame { id: s.id, pos: s.pos, halite: s.halite } ).collect(),
+                dropoffs: p.dropoffs.iter().map(|&(id,pos)| DropoffFrame { id, pos } ).collect(),
+            }
+        }).collect();
+        let map_updates = self.changed_cells.iter().map(|&pos| MapUpdate { pos, halite: self.cell( pos ) } ).collect();
+        self.changed_cells.clear();
+        TurnFrame {
+            turn_num: self.turn + 1,
+            players,
+            map_updates,
+        }
+    }
+
+    //halite stored by each player, indexed by player id
+    pub fn scores( & self ) -> Vec<usize> {
+        self.players.iter().map(|p| p.halite ).collect()
+    }
+
+    //player ids from first to last place, ties broken by lower id
+    pub fn rankings( & self ) -> Vec<usize> {
+        let mut ids = self.players.iter().map(|p| p.id ).collect::<Vec<_>>();
+        ids.sort_by(|a,b| self.players[*b].halite.cmp( &self.players[*a].halite ).then( a.cmp(b) ) );
+        ids
+    }
+
+    //plays the game to the end, asking for every player's commands each turn,
+    //observe sees each frame together with the commands all players answered with and the
+    //events the turn ended up with
+    pub fn run<F,O>( & mut self, mut player: F, mut observe: O )
+        where F: FnMut( usize, &TurnFrame ) -> Vec<Command>, O: FnMut( &TurnFrame, &[Vec<Command>], &[SimEvent] ) {
+        while !self.is_finished() {
+            let frame = self.turn_frame();
+            let commands = (0..self.players.len()).map(|id| player( id, &frame ) ).collect::<Vec<_>>();
+            self.step( &commands );
+            observe( &frame, &commands, &self.events );
+        }
+    }
+
+    //processes one turn, commands are indexed by player id, invalid commands are ignored
+    pub fn step( & mut self, commands: &[Vec<Command>] ) {
+
+        self.events.clear();
+        let c = self.constants.clone();
+
+        let mut stationary : BTreeSet<usize> = self.ships.keys().cloned().collect();
+        let mut spawning = vec![];
+
+        for (player_id, cmds) in commands.iter().enumerate().take( self.players.len() ) {
+
+            let mut commanded = BTreeSet::new();
+            let mut spawn = false;
+
+            for cmd in cmds {
+                let id = match cmd.ship_id() {
+                    Some(x) => x,
+                    None => {
+                        spawn = true;
+                        continue;
+                    },
+                };
+                let valid = self.ships.get(&id).is_some_and(|s| s.owner == player_id );
+                if !valid || !commanded.insert(id) {
+                    continue;
+                }
+                match *cmd {
+                    Command::ConvertToDropoff(_) => {
+                        let ship = self.ships[&id];
+                        let on_structure = self.structure_owner( ship.pos ).is_some();
+                        //cargo and the halite under the ship are credited towards the cost
+                        let available = self.players[player_id].halite + ship.halite + self.cell( ship.pos );
+                        if !on_structure && available >= c.dropoff_cost {
+                            self.players[player_id].halite = available - c.dropoff_cost;
+                            self.set_cell( ship.pos, 0 );
+                            self.ships.remove(&id);
+                            stationary.remove(&id);
+                            let dropoff_id = self.next_dropoff_id;
+                            self.next_dropoff_id += 1;
+                            self.players[player_id].dropoffs.push( (dropoff_id, ship.pos) );
+                            self.events.push( SimEvent::Construct { owner: player_id, id: dropoff_id, pos: ship.pos } );
+                        }
+                    },
+                    Command::Move(_,dir) => {
+                        let ship = self.ships[&id];
+                        let ratio = if ship.inspired { c.inspired_move_cost_ratio } else { c.move_cost_ratio };
+                        let cost = self.cell( ship.pos ) / ratio;
+                        if ship.halite >= cost {
+                            let delta = dir.delta();
+                            let to = torus::wrap( ( ship.pos.0 + delta.0, ship.pos.1 + delta.1 ), self.dim );
+                            let s = self.ships.get_mut(&id).unwrap();
+                            s.halite -= cost;
+                            s.pos = to;
+                            stationary.remove(&id);
+                        }
+                    },
+                    _ => {},
+                }
+            }
+
+            if spawn && self.players[player_id].halite >= c.ship_cost {
+                self.players[player_id].halite -= c.ship_cost;
+                spawning.push( player_id );
+            }
+        }
+
+        for player_id in spawning {
+            let id = self.next_ship_id;
+            self.next_ship_id += 1;
+            let pos = self.players[player_id].shipyard;
+            self.ships.insert( id, SimShip { id, owner: player_id, pos, halite: 0, inspired: false } );
+            self.events.push( SimEvent::Spawn { owner: player_id, id } );
+        }
+
+        self.resolve_collisions();
+
+        //deposit cargo on friendly structures
+        let deposits = self.ships.values()
+            .filter(|s| self.structure_owner( s.pos ) == Some( s.owner ) && s.halite > 0 )
+            .map(|s| (s.id, s.owner, s.halite) )
+            .collect::<Vec<_>>();
+        for (id,owner,amount) in deposits {
+            self.players[owner].halite += amount;
+            self.players[owner].deposited += amount;
+            self.ships.get_mut(&id).unwrap().halite = 0;
+        }
+
+        //ships that did not move mine their cell
+        for id in stationary {
+            let ship = match self.ships.get(&id) {
+                Some(x) => *x,
+                None => continue,
+            };
+            let cell = self.cell( ship.pos );
+            let ratio = if ship.inspired { c.inspired_extract_ratio } else { c.extract_ratio };
+            let capacity = c.max_halite.saturating_sub( ship.halite );
+            let extracted = cell.div_ceil( ratio ).min( capacity );
+            if extracted == 0 {
+                continue;
+            }
+            let mut gained = extracted;
+            if ship.inspired {
+                let bonus = ( extracted as f64 * c.inspired_bonus_multiplier ) as usize;
+                gained += bonus.min( capacity - extracted );
+            }
+            self.set_cell( ship.pos, cell - extracted );
+            self.ships.get_mut(&id).unwrap().halite += gained;
+        }
+
+        self.update_inspiration();
+        self.turn += 1;
+    }
+
+    //every ship sharing a cell with another ship sinks, its cargo goes to the sea or the structure owner
+    fn resolve_collisions( & mut self ) {
+        let mut by_pos : BTreeMap<(i32,i32),Vec<usize>> = BTreeMap::new();
+        for s in self.ships.values() {
+            by_pos.entry( s.pos ).or_default().push( s.id );
+        }
+        for (pos,ids) in by_pos {
+            if ids.len() < 2 {
+                continue;
+            }
+            let cargo : usize = ids.iter().map(|id| self.ships[id].halite ).sum();
+            for id in ids.iter() {
+                self.ships.remove(id);
+            }
+            match self.structure_owner( pos ) {
+                Some(owner) => {
+                    self.players[owner].halite += cargo;
+                    self.players[owner].deposited += cargo;
+                },
+                None => {
+                    let cell = self.cell( pos );
+                    self.set_cell( pos, cell + cargo );
+                },
+            }
+            self.events.push( SimEvent::Collision { ships: ids } );
+        }
+    }
+
+    //a ship is inspired when enough enemy ships are within the inspiration radius
+    fn update_inspiration( & mut self ) {
+        let c = &self.constants;
+        let mut inspired = HashMap::new();
+        for s in self.ships.values() {
+            let enemies = self.ships.values()
+                .filter(|o| o.owner != s.owner && torus::wrapped_l1( s.pos, o.pos, self.dim ) <= c.inspiration_radius as i32 )
+                .count();
+            inspired.insert( s.id, c.inspiration_enabled && enemies >= c.inspiration_ship_count );
+        }
+        for (id,v) in inspired {
+            self.ships.get_mut(&id).unwrap().inspired = v;
+        }
+    }
+
+    fn structure_owner( & self, pos: (i32,i32) ) -> Option<usize> {
+        self.players.iter()
+            .find(|p| p.shipyard == pos || p.dropoffs.iter().any(|d| d.1 == pos ) )
+            .map(|p| p.id )
+    }
+
+    fn cell( & self, pos: (i32,i32) ) -> usize {
+        self.halite[pos.0 as usize][pos.1 as usize]
+    }
+
+    fn set_cell( & mut self, pos: (i32,i32), v: usize ) {
+        self.halite[pos.0 as usize][pos.1 as usize] = v;
+        self.changed_cells.insert( pos );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use hlt::command::Direction;
+
+    //two players on an empty 8x8 map with the official defaults, shipyards at (1,1) and (6,6)
+    fn engine() -> Engine {
+        Engine::new( Constants::default(), vec![ vec![ 0; 8 ]; 8 ], &[ (1,1), (6,6) ] )
+    }
+
+    fn add_ship( e: & mut Engine, id: usize, owner: usize, pos: (i32,i32), halite: usize ) {
+        e.ships.insert( id, SimShip { id, owner, pos, halite, inspired: false } );
+    }
+
+    #[test]
+    fn players_start_with_the_initial_halite() {
+        let e = engine();
+        assert_eq!( e.scores(), vec![ 5000, 5000 ] );
+    }
+
+    #[test]
+    fn extraction_rounds_up() {
+        let mut e = engine();
+        e.set_cell( (3,3), 10 );
+        add_ship( & mut e, 100, 0, (3,3), 0 );
+        e.step( &[ vec![ Command::Stay(100) ], vec![] ] );
+        assert_eq!( e.ships[&100].halite, 3 );
+        assert_eq!( e.cell( (3,3) ), 7 );
+    }
+
+    #[test]
+    fn moving_costs_a_tenth_of_the_cell() {
+        let mut e = engine();
+        e.set_cell( (3,3), 95 );
+        e.set_cell( (5,5), 95 );
+        add_ship( & mut e, 100, 0, (3,3), 9 );
+        add_ship( & mut e, 101, 0, (5,5), 8 );
+        e.step( &[ vec![ Command::Move(100,Direction::East), Command::Move(101,Direction::East) ], vec![] ] );
+        assert_eq!( e.ships[&100].pos, (3,4) );
+        assert_eq!( e.ships[&100].halite, 0 );
+        //short of the cost the ship stays and mines instead
+        assert_eq!( e.ships[&101].pos, (5,5) );
+        assert_eq!( e.ships[&101].halite, 8 + 24 );
+        assert_eq!( e.cell( (5,5) ), 71 );
+    }
+
+    #[test]
+    fn cargo_is_capped() {
+        let mut e = engine();
+        e.set_cell( (3,3), 100 );
+        add_ship( & mut e, 100, 0, (3,3), 990 );
+        e.step( &[ vec![], vec![] ] );
+        assert_eq!( e.ships[&100].halite, 1000 );
+        assert_eq!( e.cell( (3,3) ), 90 );
+    }
+
+    #[test]
+    fn collision_at_sea_drops_the_cargo_into_the_cell() {
+        let mut e = engine();
+        add_ship( & mut e, 100, 0, (3,3), 200 );
+        add_ship( & mut e, 101, 1, (3,5), 300 );
+        e.step( &[ vec![ Command::Move(100,Direction::East) ], vec![ Command::Move(101,Direction::West) ] ] );
+        assert!( e.ships.is_empty() );
+        assert_eq!( e.cell( (3,4) ), 500 );
+        assert_eq!( e.scores(), vec![ 5000, 5000 ] );
+        match e.events.as_slice() {
+            [ SimEvent::Collision { ships } ] => assert_eq!( ships, &vec![ 100, 101 ] ),
+            _ => panic!("expected one collision"),
+        }
+    }
+
+    #[test]
+    fn collision_on_a_structure_goes_to_its_owner() {
+        let mut e = engine();
+        add_ship( & mut e, 100, 0, (1,1), 0 );
+        add_ship( & mut e, 101, 1, (1,2), 300 );
+        e.step( &[ vec![], vec![ Command::Move(101,Direction::West) ] ] );
+        assert!( e.ships.is_empty() );
+        assert_eq!( e.cell( (1,1) ), 0 );
+        assert_eq!( e.scores(), vec![ 5300, 5000 ] );
+        assert_eq!( e.players[0].deposited, 300 );
+    }
+
+    #[test]
+    fn inspired_ship_mines_with_the_bonus() {
+        let mut e = engine();
+        e.set_cell( (3,3), 100 );
+        add_ship( & mut e, 100, 0, (3,3), 0 );
+        add_ship( & mut e, 101, 1, (3,5), 0 );
+        add_ship( & mut e, 102, 1, (5,3), 0 );
+        e.step( &[ vec![], vec![] ] );
+        //inspiration is decided at the end of the turn and counts from the next one
+        assert_eq!( e.ships[&100].halite, 25 );
+        assert!( e.ships[&100].inspired );
+        assert!( !e.ships[&101].inspired && !e.ships[&102].inspired );
+        e.step( &[ vec![], vec![] ] );
+        assert_eq!( e.ships[&100].halite, 25 + 19 + 38 );
+        assert_eq!( e.cell( (3,3) ), 56 );
+    }
+
+    #[test]
+    fn dropoff_cost_is_discounted_by_cargo_and_cell() {
+        let mut e = engine();
+        e.players[0].halite = 3000;
+        e.set_cell( (3,3), 500 );
+        add_ship( & mut e, 100, 0, (3,3), 600 );
+        e.players[1].halite = 2000;
+        e.set_cell( (5,5), 500 );
+        add_ship( & mut e, 101, 1, (5,5), 600 );
+        e.step( &[ vec![ Command::ConvertToDropoff(100) ], vec![ Command::ConvertToDropoff(101) ] ] );
+        assert_eq!( e.players[0].halite, 100 );
+        assert_eq!( e.players[0].dropoffs, vec![ (0,(3,3)) ] );
+        assert_eq!( e.cell( (3,3) ), 0 );
+        assert!( !e.ships.contains_key( &100 ) );
+        //short of the cost nothing changes hands
+        assert_eq!( e.players[1].halite, 2000 );
+        assert!( e.players[1].dropoffs.is_empty() );
+        assert!( e.ships.contains_key( &101 ) );
+    }
+}